@@ -0,0 +1,26 @@
+//! Lays out a `LayoutAssetGraphArgs` JSON file (e.g. `small_graph.json`) and
+//! prints the resulting `AssetGraphLayout`.
+//!
+//!     cargo run --example layout -- small_graph.json
+
+use std::{env, fs, process};
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: cargo run --example layout -- <graph.json>");
+            process::exit(1);
+        }
+    };
+
+    let args = match fs::read_to_string(&path) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error: could not read {}: {}", path, err);
+            process::exit(1);
+        }
+    };
+
+    println!("{}", dagre_wasm::layout_asset_graph_json(&args));
+}
@@ -0,0 +1,205 @@
+use crate::graph::GraphExt;
+use graphlib_rust::Graph;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Debug;
+
+/// Which edges a traversal follows out of each node. Undirected graphs are
+/// always walked along neighbors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    /// Downstream, along out-edges.
+    #[default]
+    Successors,
+    /// Upstream, along in-edges.
+    Predecessors,
+    /// Both ways, ignoring edge direction.
+    Neighbors,
+}
+
+/// Breadth-first traversal from the seeds `vs`, returning nodes in the order
+/// they were reached. All seeds start on the first level, in the order given.
+/// Returns an empty list if any of them isn't in the graph, like `preorder`.
+pub fn bfs<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    vs: &[String],
+    direction: Direction,
+) -> Vec<String> {
+    if vs.iter().any(|v| !g.has_node(v)) {
+        return vec![];
+    }
+
+    let mut acc: Vec<String> = vec![];
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut queue: VecDeque<String> = VecDeque::new();
+    for v in vs {
+        if visited.insert(v.clone()) {
+            queue.push_back(v.clone());
+        }
+    }
+    while let Some(v) = queue.pop_front() {
+        for w in next(g, &v, direction) {
+            if visited.insert(w.clone()) {
+                queue.push_back(w);
+            }
+        }
+        acc.push(v);
+    }
+
+    acc
+}
+
+/// Nodes within `max_depth` hops of `source`, mapped to their distance in
+/// hops. The source itself is at distance 0. Empty if `source` isn't in the
+/// graph.
+pub fn bfs_limited<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    source: &String,
+    max_depth: usize,
+    direction: Direction,
+) -> HashMap<String, usize> {
+    let mut depths: HashMap<String, usize> = HashMap::new();
+    if !g.has_node(source) {
+        return depths;
+    }
+
+    depths.insert(source.clone(), 0);
+    let mut queue: VecDeque<(String, usize)> = VecDeque::from([(source.clone(), 0)]);
+    while let Some((v, depth)) = queue.pop_front() {
+        if depth == max_depth {
+            continue;
+        }
+        for w in next(g, &v, direction) {
+            if !depths.contains_key(&w) {
+                depths.insert(w.clone(), depth + 1);
+                queue.push_back((w, depth + 1));
+            }
+        }
+    }
+
+    depths
+}
+
+/// Every node `source` depends on, directly or not, nearest first: a
+/// breadth-first walk over predecessors, without `source` itself.
+pub fn upstream<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    source: &String,
+) -> Vec<String> {
+    reachable(g, source, Direction::Predecessors)
+}
+
+/// Every node that depends on `source`, directly or not, nearest first: a
+/// breadth-first walk over successors, without `source` itself.
+pub fn downstream<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    source: &String,
+) -> Vec<String> {
+    reachable(g, source, Direction::Successors)
+}
+
+fn reachable<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    source: &String,
+    direction: Direction,
+) -> Vec<String> {
+    let mut visited = bfs(g, std::slice::from_ref(source), direction);
+    visited.retain(|v| v != source);
+    visited
+}
+
+// The nodes one step from `v` in `direction`.
+pub(crate) fn next<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    v: &String,
+    direction: Direction,
+) -> Vec<String> {
+    let next = match direction {
+        _ if !g.is_directed() => g.adjacent(v),
+        Direction::Successors => g.successors(v),
+        Direction::Predecessors => g.predecessors(v),
+        Direction::Neighbors => g.adjacent(v),
+    };
+    next.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::digraph;
+
+    #[test]
+    fn visits_a_dag_level_by_level() {
+        //    a
+        //   / \
+        //  b   c
+        //  |\  |
+        //  d e |
+        //   \ /
+        //    f
+        let g = digraph(&[
+            ("a", "b"),
+            ("a", "c"),
+            ("b", "d"),
+            ("b", "e"),
+            ("c", "f"),
+            ("e", "f"),
+        ]);
+        let seeds = |vs: &[&str]| vs.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            bfs(&g, &seeds(&["a"]), Direction::Successors),
+            vec!["a", "b", "c", "d", "e", "f"]
+        );
+        assert_eq!(
+            bfs(&g, &seeds(&["f"]), Direction::Predecessors),
+            vec!["f", "c", "e", "a", "b"]
+        );
+        assert_eq!(
+            bfs(&g, &seeds(&["d", "c"]), Direction::Successors),
+            vec!["d", "c", "f"]
+        );
+        assert!(bfs(&g, &seeds(&["a", "missing"]), Direction::Successors).is_empty());
+    }
+
+    #[test]
+    fn bfs_limited_stops_at_max_depth() {
+        let g = digraph(&[("a", "b"), ("b", "c"), ("c", "d")]);
+        let b = "b".to_string();
+        let depths = |max_depth, direction| {
+            let mut depths: Vec<(String, usize)> = bfs_limited(&g, &b, max_depth, direction)
+                .into_iter()
+                .collect();
+            depths.sort();
+            depths
+        };
+        let pairs = |pairs: &[(&str, usize)]| {
+            pairs
+                .iter()
+                .map(|(v, depth)| (v.to_string(), *depth))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            depths(1, Direction::Successors),
+            pairs(&[("b", 0), ("c", 1)])
+        );
+        assert_eq!(
+            depths(2, Direction::Successors),
+            pairs(&[("b", 0), ("c", 1), ("d", 2)])
+        );
+        assert_eq!(
+            depths(1, Direction::Neighbors),
+            pairs(&[("a", 1), ("b", 0), ("c", 1)])
+        );
+        assert_eq!(depths(0, Direction::Predecessors), pairs(&[("b", 0)]));
+    }
+
+    #[test]
+    fn upstream_and_downstream_along_a_chain() {
+        let g = digraph(&[("a", "b"), ("b", "c"), ("c", "d")]);
+        let (a, c) = ("a".to_string(), "c".to_string());
+        assert_eq!(upstream(&g, &c), vec!["b", "a"]);
+        assert_eq!(downstream(&g, &c), vec!["d"]);
+        assert_eq!(downstream(&g, &a), vec!["b", "c", "d"]);
+        assert!(upstream(&g, &a).is_empty());
+        assert!(downstream(&g, &"missing".to_string()).is_empty());
+    }
+}
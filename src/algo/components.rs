@@ -0,0 +1,33 @@
+use crate::graph::GraphExt;
+use graphlib_rust::Graph;
+use std::collections::HashSet;
+use std::fmt::Debug;
+
+/// Groups the nodes into connected components, ignoring edge direction. Each
+/// component lists its nodes in the order they were reached.
+pub fn components<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+) -> Vec<Vec<String>> {
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut components: Vec<Vec<String>> = vec![];
+
+    for v in g.nodes() {
+        if !visited.insert(v.clone()) {
+            continue;
+        }
+
+        let mut component: Vec<String> = vec![];
+        let mut stack: Vec<String> = vec![v];
+        while let Some(u) = stack.pop() {
+            for w in g.adjacent(&u).unwrap_or_default() {
+                if visited.insert(w.clone()) {
+                    stack.push(w);
+                }
+            }
+            component.push(u);
+        }
+        components.push(component);
+    }
+
+    components
+}
@@ -0,0 +1,43 @@
+use crate::algo::find_cycles;
+use graphlib_rust::Graph;
+use std::fmt::Debug;
+
+/// Returns the cycles, as in `find_cycles`, that contain `v`. Each node is
+/// in at most one strongly connected component, so this is a single cycle,
+/// or empty if `v` isn't on any cycle or isn't in the graph.
+pub fn cycles_through<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    v: &String,
+) -> Vec<Vec<String>> {
+    if !g.has_node(v) {
+        return vec![];
+    }
+    find_cycles(g)
+        .into_iter()
+        .filter(|cmpt| cmpt.contains(v))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::digraph;
+
+    #[test]
+    fn returns_only_the_cycle_holding_the_node() {
+        let g = digraph(&[
+            ("a", "b"),
+            ("b", "c"),
+            ("c", "a"),
+            ("x", "y"),
+            ("y", "x"),
+            ("c", "x"),
+            ("y", "z"),
+        ]);
+        let mut cycles = cycles_through(&g, &"b".to_string());
+        cycles.iter_mut().for_each(|cycle| cycle.sort());
+        assert_eq!(cycles, vec![vec!["a", "b", "c"]]);
+        assert!(cycles_through(&g, &"z".to_string()).is_empty());
+        assert!(cycles_through(&g, &"missing".to_string()).is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::graph::GraphExt;
+use graphlib_rust::Graph;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::Debug;
+
+/// Pre- or post-order depth-first traversal from each of `vs`, returning nodes
+/// in the order they were visited. Directed graphs are walked along
+/// successors and undirected graphs along neighbors. Any `order` other than
+/// `"post"` is treated as `"pre"`.
+///
+/// Unlike graphlib_rust's `dfs`, the pre-order walk doesn't index past the end
+/// of a node's successors, and the graph is only borrowed.
+pub fn dfs<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    vs: &[String],
+    order: &str,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    // Successors that aren't nodes of `g` are skipped rather than visited.
+    let navigation = |v: &String| -> Vec<String> {
+        let next = if g.is_directed() {
+            g.successors(v)
+        } else {
+            g.adjacent(v)
+        };
+        next.unwrap_or_default()
+            .into_iter()
+            .filter(|w| g.has_node(w))
+            .collect()
+    };
+
+    let mut acc: Vec<String> = vec![];
+    let mut visited: HashSet<String> = HashSet::new();
+    for v in vs {
+        if !g.has_node(v) {
+            return Err(format!("Graph does not have node: {}", v).into());
+        }
+
+        if order == "post" {
+            post_order_dfs(v, &navigation, &mut visited, &mut acc);
+        } else {
+            pre_order_dfs(v, &navigation, &mut visited, &mut acc);
+        }
+    }
+
+    Ok(acc)
+}
+
+/// `dfs` seeded from every source, followed by any nodes the sources can't
+/// reach (such as members of a cycle with no way in), so every node is
+/// visited exactly once.
+pub fn dfs_all<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    order: &str,
+) -> Vec<String> {
+    let mut seeds = g.sources();
+    seeds.extend(g.nodes());
+    dfs(g, &seeds, order).unwrap_or_default()
+}
+
+fn pre_order_dfs(
+    v: &str,
+    navigation: &dyn Fn(&String) -> Vec<String>,
+    visited: &mut HashSet<String>,
+    acc: &mut Vec<String>,
+) {
+    let mut stack: Vec<String> = vec![v.to_string()];
+    while let Some(curr) = stack.pop() {
+        if !visited.insert(curr.clone()) {
+            continue;
+        }
+        // Pushed in reverse so the first successor is visited first.
+        stack.extend(navigation(&curr).into_iter().rev());
+        acc.push(curr);
+    }
+}
+
+fn post_order_dfs(
+    v: &str,
+    navigation: &dyn Fn(&String) -> Vec<String>,
+    visited: &mut HashSet<String>,
+    acc: &mut Vec<String>,
+) {
+    // The flag marks a node whose successors have all been pushed, so it is
+    // emitted the next time it comes off the stack.
+    let mut stack: Vec<(String, bool)> = vec![(v.to_string(), false)];
+    while let Some((curr, done)) = stack.pop() {
+        if done {
+            acc.push(curr);
+            continue;
+        }
+        if !visited.insert(curr.clone()) {
+            continue;
+        }
+        let successors = navigation(&curr);
+        stack.push((curr, true));
+        stack.extend(successors.into_iter().rev().map(|w| (w, false)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::digraph;
+
+    #[test]
+    fn skips_a_removed_node() {
+        let mut g = digraph(&[("a", "b"), ("b", "c"), ("a", "d")]);
+        g.remove_node(&"b".to_string());
+        assert_eq!(dfs(&g, &["a".to_string()], "pre").unwrap(), vec!["a", "d"]);
+        assert!(dfs(&g, &["b".to_string()], "pre").is_err());
+        assert_eq!(dfs_all(&g, "post"), vec!["d", "a", "c"]);
+    }
+
+    #[test]
+    fn dfs_all_visits_every_node_once() {
+        // c <-> d has no way in from the source a.
+        let g = digraph(&[("a", "b"), ("c", "d"), ("d", "c"), ("d", "b")]);
+        let visited = dfs_all(&g, "pre");
+        assert_eq!(visited, vec!["a", "b", "c", "d"]);
+    }
+}
@@ -0,0 +1,52 @@
+use graphlib_rust::{Edge, Graph};
+use std::collections::HashSet;
+use std::fmt::Debug;
+
+/// A feedback arc set made of the back edges of a depth-first search, started
+/// from each unvisited node in insertion order. This is the cycle breaking
+/// dagre does by default: cheaper than `greedy_fas`, but the result depends
+/// on where the search starts and can be larger. Self-loops are included.
+pub fn dfs_fas<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+) -> Vec<Edge> {
+    let mut fas: Vec<Edge> = vec![];
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut on_stack: HashSet<String> = HashSet::new();
+
+    for v in g.nodes() {
+        if visited.contains(&v) {
+            continue;
+        }
+        // Each frame is a node and the out-edges still to be followed from
+        // it, kept reversed so they can be popped in order.
+        let mut stack: Vec<(String, Vec<Edge>)> = vec![(v.clone(), out_edges_reversed(g, &v))];
+        visited.insert(v.clone());
+        on_stack.insert(v);
+        while let Some((u, edges)) = stack.last_mut() {
+            match edges.pop() {
+                Some(e) if on_stack.contains(&e.w) => fas.push(e),
+                Some(e) if visited.insert(e.w.clone()) => {
+                    on_stack.insert(e.w.clone());
+                    let next = out_edges_reversed(g, &e.w);
+                    stack.push((e.w, next));
+                }
+                Some(_) => {}
+                None => {
+                    on_stack.remove(u.as_str());
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    fas
+}
+
+fn out_edges_reversed<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    v: &String,
+) -> Vec<Edge> {
+    let mut edges = g.out_edges(v, None).unwrap_or_default();
+    edges.reverse();
+    edges
+}
@@ -0,0 +1,166 @@
+use graphlib_rust::{Edge, Graph};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::error::Error;
+use std::fmt::Debug;
+
+/// Lists the edges to follow out of a node.
+pub type EdgeFn<'a> = &'a dyn Fn(&String) -> Vec<Edge>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DijkstraEntry {
+    pub distance: f64,
+    pub predecessor: Option<String>,
+}
+
+struct QueueEntry {
+    distance: f64,
+    v: String,
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance == other.distance
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    // Reversed so the BinaryHeap pops the closest node first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+/// Single-source shortest paths. `weight_fn` derives the weight of each edge and
+/// `edge_fn` lists the edges to follow from a node, defaulting to `out_edges`.
+/// Nodes that can't be reached keep an infinite distance and no predecessor.
+pub fn dijkstra<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    source: &String,
+    weight_fn: impl Fn(&Edge) -> f64,
+    edge_fn: Option<EdgeFn>,
+) -> Result<HashMap<String, DijkstraEntry>, Box<dyn Error>> {
+    if !g.has_node(source) {
+        return Err(format!("Graph does not have node: {}", source).into());
+    }
+
+    let mut results: HashMap<String, DijkstraEntry> = HashMap::new();
+    for v in g.nodes() {
+        let distance = if &v == source { 0.0 } else { f64::INFINITY };
+        results.insert(
+            v,
+            DijkstraEntry {
+                distance,
+                predecessor: None,
+            },
+        );
+    }
+
+    let mut queue: BinaryHeap<QueueEntry> = BinaryHeap::new();
+    queue.push(QueueEntry {
+        distance: 0.0,
+        v: source.clone(),
+    });
+
+    while let Some(QueueEntry { distance, v }) = queue.pop() {
+        // Skip stale queue entries left behind by a later decrease.
+        if distance > results[&v].distance {
+            continue;
+        }
+
+        let edges = match edge_fn {
+            Some(edge_fn) => edge_fn(&v),
+            None => g.out_edges(&v, None).unwrap_or_default(),
+        };
+
+        for edge in edges {
+            let w = if edge.v != v { &edge.v } else { &edge.w };
+            let weight = weight_fn(&edge);
+            if weight < 0.0 {
+                return Err(format!(
+                    "dijkstra does not allow negative edge weights. Bad edge: {:?} Weight: {}",
+                    edge, weight
+                )
+                .into());
+            }
+
+            let w_distance = distance + weight;
+            if let Some(w_entry) = results.get_mut(w) {
+                if w_distance < w_entry.distance {
+                    w_entry.distance = w_distance;
+                    w_entry.predecessor = Some(v.clone());
+                    queue.push(QueueEntry {
+                        distance: w_distance,
+                        v: w.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::digraph;
+
+    fn weights<'a>(table: &'a [(&str, &str, f64)]) -> impl Fn(&Edge) -> f64 + 'a {
+        move |e| {
+            table
+                .iter()
+                .find(|(v, w, _)| e.v == *v && e.w == *w)
+                .map_or(1.0, |(_, _, weight)| *weight)
+        }
+    }
+
+    #[test]
+    fn takes_the_cheaper_of_two_routes() {
+        let g = digraph(&[("a", "b"), ("b", "c"), ("a", "c"), ("c", "d")]);
+        let table = [("a", "b", 1.0), ("b", "c", 2.0), ("a", "c", 5.0)];
+        let results = dijkstra(&g, &"a".to_string(), weights(&table), None).unwrap();
+
+        assert_eq!(results["a"].distance, 0.0);
+        assert_eq!(results["a"].predecessor, None);
+        assert_eq!(results["c"].distance, 3.0);
+        assert_eq!(results["c"].predecessor.as_deref(), Some("b"));
+        assert_eq!(results["d"].distance, 4.0);
+    }
+
+    #[test]
+    fn leaves_unreachable_nodes_at_infinity() {
+        let mut g = digraph(&[("a", "b")]);
+        g.set_node("z".to_string(), None);
+        let results = dijkstra(&g, &"b".to_string(), |_| 1.0, None).unwrap();
+        assert_eq!(results["b"].distance, 0.0);
+        assert!(results["a"].distance.is_infinite());
+        assert!(results["z"].distance.is_infinite());
+        assert_eq!(results["z"].predecessor, None);
+    }
+
+    #[test]
+    fn follows_edge_fn_in_both_directions() {
+        let g = digraph(&[("a", "b"), ("c", "b")]);
+        let edge_fn = |v: &String| g.node_edges(v, None).unwrap_or_default();
+        let results = dijkstra(&g, &"a".to_string(), |_| 1.0, Some(&edge_fn)).unwrap();
+        assert_eq!(results["c"].distance, 2.0);
+        assert_eq!(results["c"].predecessor.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn rejects_negative_weights_and_missing_sources() {
+        let g = digraph(&[("a", "b")]);
+        let table = [("a", "b", -1.0)];
+        assert!(dijkstra(&g, &"a".to_string(), weights(&table), None).is_err());
+        assert!(dijkstra(&g, &"missing".to_string(), |_| 1.0, None).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use crate::algo::tarjan;
+use graphlib_rust::Graph;
+use std::fmt::Debug;
+
+/// Returns the strongly connected components that contain a cycle: those with
+/// more than one node, or a single node with a self-loop.
+pub fn find_cycles<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+) -> Vec<Vec<String>> {
+    tarjan(g)
+        .into_iter()
+        .filter(|cmpt| cmpt.len() > 1 || (cmpt.len() == 1 && g.has_edge(&cmpt[0], &cmpt[0], None)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::is_acyclic;
+    use crate::graph::tests::digraph;
+
+    #[test]
+    fn reports_self_loops_and_multi_node_cycles() {
+        let g = digraph(&[("a", "b"), ("b", "a"), ("b", "c"), ("d", "d")]);
+        let mut cycles = find_cycles(&g);
+        for cycle in cycles.iter_mut() {
+            cycle.sort();
+        }
+        cycles.sort();
+        assert_eq!(cycles, vec![vec!["a", "b"], vec!["d"]]);
+        assert!(!is_acyclic(&g));
+    }
+
+    #[test]
+    fn a_dag_has_no_cycles() {
+        let g = digraph(&[("a", "b"), ("b", "c"), ("a", "c")]);
+        assert!(find_cycles(&g).is_empty());
+        assert!(is_acyclic(&g));
+    }
+}
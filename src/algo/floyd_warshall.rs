@@ -0,0 +1,121 @@
+use crate::algo::{DijkstraEntry, EdgeFn};
+use graphlib_rust::{Edge, Graph};
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// All-pairs shortest paths. `edge_fn` defaults to `out_edges` for directed
+/// graphs and `node_edges` for undirected ones. Every node is at distance 0 from
+/// itself, and unreachable pairs keep an infinite distance.
+pub fn floyd_warshall<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    weight_fn: impl Fn(&Edge) -> f64,
+    edge_fn: Option<EdgeFn>,
+) -> HashMap<String, HashMap<String, DijkstraEntry>> {
+    let nodes = g.nodes();
+    let index: HashMap<&String, usize> = nodes.iter().enumerate().map(|(i, v)| (v, i)).collect();
+    let n = nodes.len();
+
+    let mut distance: Vec<Vec<f64>> = vec![vec![f64::INFINITY; n]; n];
+    let mut predecessor: Vec<Vec<Option<usize>>> = vec![vec![None; n]; n];
+
+    for (i, v) in nodes.iter().enumerate() {
+        distance[i][i] = 0.0;
+
+        let edges = match edge_fn {
+            Some(edge_fn) => edge_fn(v),
+            None if g.is_directed() => g.out_edges(v, None).unwrap_or_default(),
+            None => g.node_edges(v, None).unwrap_or_default(),
+        };
+        for edge in edges {
+            let w = if &edge.v == v { &edge.w } else { &edge.v };
+            if let Some(&j) = index.get(w) {
+                let d = weight_fn(&edge);
+                if d < distance[i][j] {
+                    distance[i][j] = d;
+                    predecessor[i][j] = Some(i);
+                }
+            }
+        }
+    }
+
+    for k in 0..n {
+        for i in 0..n {
+            for j in 0..n {
+                let alt = distance[i][k] + distance[k][j];
+                if alt < distance[i][j] {
+                    distance[i][j] = alt;
+                    predecessor[i][j] = predecessor[k][j];
+                }
+            }
+        }
+    }
+
+    let mut results: HashMap<String, HashMap<String, DijkstraEntry>> = HashMap::new();
+    for (i, v) in nodes.iter().enumerate() {
+        let row = nodes
+            .iter()
+            .enumerate()
+            .map(|(j, w)| {
+                (
+                    w.clone(),
+                    DijkstraEntry {
+                        distance: distance[i][j],
+                        predecessor: predecessor[i][j].map(|p| nodes[p].clone()),
+                    },
+                )
+            })
+            .collect();
+        results.insert(v.clone(), row);
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::dijkstra;
+    use crate::graph::tests::digraph;
+
+    fn weight(e: &Edge) -> f64 {
+        match (e.v.as_str(), e.w.as_str()) {
+            ("a", "c") => 5.0,
+            ("b", "c") => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    #[test]
+    fn agrees_with_dijkstra_from_every_source() {
+        let mut g = digraph(&[("a", "b"), ("b", "c"), ("a", "c"), ("c", "d"), ("d", "b")]);
+        g.set_node("z".to_string(), None);
+        let all = floyd_warshall(&g, weight, None);
+
+        for v in g.nodes() {
+            let single = dijkstra(&g, &v, weight, None).unwrap();
+            for w in g.nodes() {
+                assert_eq!(all[&v][&w].distance, single[&w].distance, "{} -> {}", v, w);
+            }
+        }
+        assert_eq!(all["a"]["c"].distance, 3.0);
+        assert_eq!(all["a"]["c"].predecessor.as_deref(), Some("b"));
+        assert_eq!(all["z"]["z"].distance, 0.0);
+        assert!(all["z"]["a"].distance.is_infinite());
+    }
+
+    #[test]
+    fn undirected_graphs_follow_edges_both_ways() {
+        let mut g: Graph<(), (), ()> = Graph::new(Some(graphlib_rust::GraphOption {
+            directed: Some(false),
+            multigraph: Some(false),
+            compound: Some(false),
+        }));
+        g.set_path(
+            &vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            None,
+        );
+        let all = floyd_warshall(&g, |_| 1.0, None);
+        assert_eq!(all["c"]["a"].distance, 2.0);
+        assert_eq!(all["a"]["c"].distance, 2.0);
+    }
+}
@@ -0,0 +1,9 @@
+use crate::algo::topsort;
+use graphlib_rust::Graph;
+use std::fmt::Debug;
+
+pub fn is_acyclic<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+) -> bool {
+    topsort(g).is_ok()
+}
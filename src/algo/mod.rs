@@ -0,0 +1,31 @@
+pub mod bfs;
+pub mod components;
+pub mod cycles_through;
+pub mod dfs;
+pub mod dfs_fas;
+pub mod dijkstra;
+pub mod find_cycles;
+pub mod floyd_warshall;
+pub mod greedy_fas;
+pub mod is_acyclic;
+pub mod postorder;
+pub mod preorder;
+pub mod prim;
+pub mod tarjan;
+pub mod topsort;
+
+pub use bfs::{bfs, bfs_limited, downstream, upstream, Direction};
+pub use components::components;
+pub use cycles_through::cycles_through;
+pub use dfs::{dfs, dfs_all};
+pub use dfs_fas::dfs_fas;
+pub use dijkstra::{dijkstra, DijkstraEntry, EdgeFn};
+pub use find_cycles::find_cycles;
+pub use floyd_warshall::floyd_warshall;
+pub use greedy_fas::greedy_fas;
+pub use is_acyclic::is_acyclic;
+pub use postorder::postorder;
+pub use preorder::preorder;
+pub use prim::prim;
+pub use tarjan::tarjan;
+pub use topsort::topsort;
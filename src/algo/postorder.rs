@@ -0,0 +1,12 @@
+use crate::algo::dfs;
+use graphlib_rust::Graph;
+use std::fmt::Debug;
+
+/// Post-order traversal from each of `vs`. Returns an empty list if any of
+/// them isn't in the graph, like graphlib_rust's `postorder`.
+pub fn postorder<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    vs: &[String],
+) -> Vec<String> {
+    dfs(g, vs, "post").unwrap_or_default()
+}
@@ -0,0 +1,40 @@
+use crate::algo::dfs;
+use graphlib_rust::Graph;
+use std::fmt::Debug;
+
+/// Pre-order traversal from each of `vs`. Returns an empty list if any of
+/// them isn't in the graph, like graphlib_rust's `preorder`.
+pub fn preorder<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    vs: &[String],
+) -> Vec<String> {
+    dfs(g, vs, "pre").unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algo::postorder;
+    use crate::graph::tests::digraph;
+
+    #[test]
+    fn visits_a_tree_left_to_right() {
+        //       a
+        //     /   \
+        //    b     e
+        //   / \     \
+        //  c   d     f
+        let g = digraph(&[("a", "b"), ("a", "e"), ("b", "c"), ("b", "d"), ("e", "f")]);
+        let root = ["a".to_string()];
+        assert_eq!(preorder(&g, &root), vec!["a", "b", "c", "d", "e", "f"]);
+        assert_eq!(postorder(&g, &root), vec!["c", "d", "b", "f", "e", "a"]);
+        assert_eq!(preorder(&g, &["e".to_string()]), vec!["e", "f"]);
+    }
+
+    #[test]
+    fn leaves_and_missing_roots() {
+        let g = digraph(&[("a", "b")]);
+        assert_eq!(preorder(&g, &["b".to_string()]), vec!["b"]);
+        assert!(preorder(&g, &["missing".to_string()]).is_empty());
+    }
+}
@@ -0,0 +1,132 @@
+use graphlib_rust::{Edge, Graph, GraphOption};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::fmt::Debug;
+
+struct Candidate {
+    weight: f64,
+    // Insertion order, so equal weights are taken first come first served.
+    seq: usize,
+    edge: Edge,
+    to: String,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    // Reversed so the BinaryHeap pops the lightest edge first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .weight
+            .total_cmp(&self.weight)
+            .then(other.seq.cmp(&self.seq))
+    }
+}
+
+/// Minimum spanning tree by Prim's algorithm, ignoring edge direction.
+/// `weight_fn` derives the weight of each edge. Returns an undirected graph
+/// with every node and its label, plus the tree edges and their labels. A
+/// disconnected graph gives a spanning forest, one tree per component.
+pub fn prim<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+    weight_fn: impl Fn(&Edge) -> f64,
+) -> Graph<GL, N, E> {
+    let mut tree: Graph<GL, N, E> = Graph::new(Some(GraphOption {
+        directed: Some(false),
+        multigraph: Some(false),
+        compound: Some(false),
+    }));
+    for v in g.nodes() {
+        tree.set_node(v.clone(), g.node(&v).cloned());
+    }
+
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut queue: BinaryHeap<Candidate> = BinaryHeap::new();
+    let mut seq = 0;
+    for root in g.nodes() {
+        if visited.contains(&root) {
+            continue;
+        }
+
+        let mut next = Some(root);
+        while let Some(v) = next.take() {
+            visited.insert(v.clone());
+            for edge in g.node_edges(&v, None).unwrap_or_default() {
+                let to = if edge.v == v { &edge.w } else { &edge.v };
+                if !visited.contains(to) {
+                    queue.push(Candidate {
+                        weight: weight_fn(&edge),
+                        seq,
+                        to: to.clone(),
+                        edge,
+                    });
+                    seq += 1;
+                }
+            }
+            while let Some(candidate) = queue.pop() {
+                if visited.contains(&candidate.to) {
+                    continue;
+                }
+                let label = g.edge_with_obj(&candidate.edge).cloned();
+                let _ = tree.set_edge(&candidate.edge.v, &candidate.edge.w, label, None);
+                next = Some(candidate.to);
+                break;
+            }
+        }
+    }
+
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::digraph;
+
+    #[test]
+    fn spans_each_component_with_its_lightest_edges() {
+        let table = [
+            ("a", "b", 1.0),
+            ("b", "c", 2.0),
+            ("a", "c", 3.0),
+            ("c", "d", 4.0),
+            ("b", "d", 5.0),
+            ("e", "f", 1.0),
+        ];
+        let edges: Vec<(&str, &str)> = table.iter().map(|(v, w, _)| (*v, *w)).collect();
+        let g = digraph(&edges);
+        let weight = |e: &Edge| {
+            table
+                .iter()
+                .find(|(v, w, _)| e.v == *v && e.w == *w)
+                .map_or(f64::INFINITY, |(_, _, weight)| *weight)
+        };
+
+        let tree = prim(&g, weight);
+        assert!(!tree.is_directed());
+        assert_eq!(tree.node_count(), 6);
+        let mut tree_edges: Vec<(String, String)> = tree
+            .edges()
+            .into_iter()
+            .map(|e| if e.v < e.w { (e.v, e.w) } else { (e.w, e.v) })
+            .collect();
+        tree_edges.sort();
+        let expected: Vec<(String, String)> = [("a", "b"), ("b", "c"), ("c", "d"), ("e", "f")]
+            .iter()
+            .map(|(v, w)| (v.to_string(), w.to_string()))
+            .collect();
+        assert_eq!(tree_edges, expected);
+    }
+}
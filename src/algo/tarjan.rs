@@ -0,0 +1,134 @@
+use graphlib_rust::Graph;
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+struct Visit {
+    index: usize,
+    lowlink: usize,
+    on_stack: bool,
+}
+
+/// Tarjan's strongly connected components algorithm. Components are returned
+/// in reverse topological order, matching graphlib. The traversal keeps its own
+/// call stack so deep graphs don't overflow the (small) WASM stack.
+pub fn tarjan<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+) -> Vec<Vec<String>> {
+    let mut index = 0;
+    let mut stack: Vec<String> = vec![];
+    let mut visited: HashMap<String, Visit> = HashMap::new();
+    let mut results: Vec<Vec<String>> = vec![];
+
+    for root in g.nodes() {
+        if visited.contains_key(&root) {
+            continue;
+        }
+
+        // Each frame is a node plus its successors and the next one to explore.
+        let mut frames: Vec<(String, Vec<String>, usize)> = vec![];
+        visit(&root, &mut index, &mut stack, &mut visited);
+        frames.push((root.clone(), g.successors(&root).unwrap_or_default(), 0));
+
+        while let Some((v, successors, next)) = frames.last_mut() {
+            if let Some(w) = successors.get(*next).cloned() {
+                *next += 1;
+                if !visited.contains_key(&w) {
+                    visit(&w, &mut index, &mut stack, &mut visited);
+                    let w_successors = g.successors(&w).unwrap_or_default();
+                    frames.push((w, w_successors, 0));
+                } else if visited[&w].on_stack {
+                    let w_index = visited[&w].index;
+                    let entry = visited.get_mut(v).unwrap();
+                    entry.lowlink = entry.lowlink.min(w_index);
+                }
+                continue;
+            }
+
+            let v = v.clone();
+            frames.pop();
+            let (v_index, v_lowlink) = (visited[&v].index, visited[&v].lowlink);
+
+            if let Some((parent, _, _)) = frames.last() {
+                let entry = visited.get_mut(parent).unwrap();
+                entry.lowlink = entry.lowlink.min(v_lowlink);
+            }
+
+            if v_lowlink == v_index {
+                let mut cmpt: Vec<String> = vec![];
+                while let Some(w) = stack.pop() {
+                    visited.get_mut(&w).unwrap().on_stack = false;
+                    let done = w == v;
+                    cmpt.push(w);
+                    if done {
+                        break;
+                    }
+                }
+                results.push(cmpt);
+            }
+        }
+    }
+
+    results
+}
+
+fn visit(
+    v: &str,
+    index: &mut usize,
+    stack: &mut Vec<String>,
+    visited: &mut HashMap<String, Visit>,
+) {
+    visited.insert(
+        v.to_string(),
+        Visit {
+            index: *index,
+            lowlink: *index,
+            on_stack: true,
+        },
+    );
+    *index += 1;
+    stack.push(v.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::tests::digraph;
+
+    fn sorted(mut components: Vec<Vec<String>>) -> Vec<Vec<String>> {
+        for cmpt in components.iter_mut() {
+            cmpt.sort();
+        }
+        components.sort();
+        components
+    }
+
+    #[test]
+    fn finds_nested_cycles_as_one_component() {
+        // b <-> c sits inside the larger cycle a -> b -> c -> d -> a; e hangs
+        // off the end and f -> g -> f is a separate cycle.
+        let g = digraph(&[
+            ("a", "b"),
+            ("b", "c"),
+            ("c", "b"),
+            ("c", "d"),
+            ("d", "a"),
+            ("d", "e"),
+            ("f", "g"),
+            ("g", "f"),
+        ]);
+        assert_eq!(
+            sorted(tarjan(&g)),
+            vec![vec!["a", "b", "c", "d"], vec!["e"], vec!["f", "g"]]
+        );
+    }
+
+    #[test]
+    fn returns_singletons_for_a_dag_in_reverse_topological_order() {
+        let g = digraph(&[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d")]);
+        let components = tarjan(&g);
+        assert!(components.iter().all(|cmpt| cmpt.len() == 1));
+        assert_eq!(components.len(), 4);
+        assert_eq!(components.first(), Some(&vec!["d".to_string()]));
+        assert_eq!(components.last(), Some(&vec!["a".to_string()]));
+    }
+}
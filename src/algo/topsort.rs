@@ -0,0 +1,50 @@
+use graphlib_rust::Graph;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::Debug;
+
+/// Topologically sorts the graph, erroring if it contains a cycle. Like graphlib,
+/// this walks predecessors back from each sink.
+pub fn topsort<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+    g: &Graph<GL, N, E>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut on_stack: HashSet<String> = HashSet::new();
+    let mut results: Vec<String> = vec![];
+
+    for sink in g.sinks() {
+        if visited.contains(&sink) {
+            continue;
+        }
+
+        visited.insert(sink.clone());
+        on_stack.insert(sink.clone());
+        let preds = g.predecessors(&sink).unwrap_or_default();
+        let mut frames: Vec<(String, Vec<String>, usize)> = vec![(sink, preds, 0)];
+
+        while let Some((v, preds, next)) = frames.last_mut() {
+            if let Some(u) = preds.get(*next).cloned() {
+                *next += 1;
+                if on_stack.contains(&u) {
+                    return Err("Graph contains a cycle".into());
+                }
+                if visited.insert(u.clone()) {
+                    on_stack.insert(u.clone());
+                    let u_preds = g.predecessors(&u).unwrap_or_default();
+                    frames.push((u, u_preds, 0));
+                }
+                continue;
+            }
+
+            on_stack.remove(v);
+            results.push(v.clone());
+            frames.pop();
+        }
+    }
+
+    if visited.len() != g.node_count() {
+        return Err("Graph contains a cycle".into());
+    }
+
+    Ok(results)
+}
@@ -0,0 +1,916 @@
+use dagre_rust::layout::add_border_segments::BorderTypeName;
+use dagre_rust::layout::util::{
+    add_dummy_node, as_non_compound_graph, normalize_ranks, remove_empty_ranks, simplify_ref,
+    transfer_node_edge_labels,
+};
+use dagre_rust::layout::*;
+use dagre_rust::{GraphConfig, GraphEdge, GraphEdgePoint, GraphNode};
+use graphlib_rust::graph::GRAPH_NODE;
+use graphlib_rust::Graph;
+use ordered_hashmap::OrderedHashMap;
+use std::collections::{HashMap, HashSet};
+
+use crate::dagre_order::{build_layer_matrix, order};
+use crate::graph::GraphExt;
+use crate::{algo, LayoutError};
+
+/// The first half of dagre_rust's `run_layout`, up to and including ordering,
+/// which is where nearly all of its time goes. `place` is the second half, so
+/// a copy of the graph between the two can be placed more than once.
+///
+/// Both follow upstream step for step, except where upstream breaks edge
+/// labels: proxy injection reads both ranks from the edge's tail and never
+/// records which edge a proxy belongs to, so `remove_edge_label_proxies`
+/// panics; the dummy meant to hold the label is tagged as a plain "edge", so
+/// its position is never copied back; and the final translation overwrites
+/// label coordinates with the offset instead of subtracting it.
+///
+/// Groups (compound nodes) need more of the pipeline replaced: upstream only
+/// adds border nodes down the left of each group, parents dummies of edges
+/// leaving a group inside it, counts the groups themselves as nodes on rank
+/// 0 while ordering, and never copies the orders it finds back to the graph.
+/// Any layout showing more than one group used to panic. The steps below
+/// marked as dagre's are ports of its JavaScript, as is `dagre_order::order`.
+///
+/// Nodes in `rank_overrides` are also moved onto the given ranks once dagre
+/// has ranked the graph; see `pin_ranks`.
+pub(crate) fn rank_and_order(
+    graph: &mut Graph<GraphConfig, GraphNode, GraphEdge>,
+    rank_overrides: &HashMap<String, i32>,
+) -> Result<(), LayoutError> {
+    make_space_for_edge_labels(graph);
+    remove_self_edges(graph);
+    acyclic::run(graph);
+    nesting_graph::run(graph);
+    let mut nc_graph = as_non_compound_graph(graph);
+    rank_feasible_tree(&mut nc_graph);
+    pin_ranks(&mut nc_graph, rank_overrides)?;
+    transfer_node_edge_labels(&nc_graph, graph);
+    inject_edge_label_proxies(graph);
+    remove_empty_ranks(graph);
+    nesting_graph::cleanup(graph);
+    normalize_ranks(graph);
+    assign_rank_min_max(graph);
+    remove_edge_label_proxies(graph);
+    normalize::run(graph);
+    tag_edge_label_dummies(graph);
+    parent_dummy_chains(graph);
+    add_border_segments(graph);
+    order(graph);
+    Ok(())
+}
+
+/// The second half of dagre_rust's `run_layout`: coordinate assignment
+/// onwards, for a graph that has been through `rank_and_order`. Nodes in
+/// `pins` are then moved along their rank to the given position, in the
+/// coordinates the layout would have without pins; see `pin_along_ranks`.
+pub(crate) fn place(
+    graph: &mut Graph<GraphConfig, GraphNode, GraphEdge>,
+    pins: &HashMap<String, f32>,
+) {
+    insert_self_edges(graph);
+    coordinate_system::adjust(graph);
+    position(graph);
+    let unpinned_origin = pin_along_ranks(graph, pins);
+    position_self_edges(graph);
+    remove_border_nodes(graph);
+    undo_normalize(graph);
+    fixup_edge_label_coords(graph);
+    coordinate_system::undo(graph);
+    translate_graph(graph, unpinned_origin);
+    assign_node_intersects(graph);
+    reverse_points_for_reversed_edges(graph);
+    acyclic::undo(graph);
+}
+
+// dagre's `position`. dagre_rust's `position_x` gathers the two kinds of
+// Brandes-Köpf conflicts with `extend`, so the type-2 conflicts filed under a
+// node replace its type-1 ones instead of joining them. A conflict is filed
+// under whichever of its ends has the id that sorts first as a string, and
+// dummy ids come from a counter shared by every layout, so the same graph
+// could be placed differently once the ids passed a power of ten. Like
+// `into_values`, `OrderedHashMap`'s `into_iter` is quadratic, so the result
+// is read with `iter`. The rest of the algorithm is dagre_rust's, but for
+// the two ports below.
+fn position(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    let mut ncg = as_non_compound_graph(g);
+    position_y(&mut ncg);
+    for (v, &x) in position_x(&mut ncg).iter() {
+        let y = ncg.node(v).unwrap().y;
+        let node = g.node_mut(v).unwrap();
+        node.x = x;
+        node.y = y;
+    }
+}
+
+fn position_y(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    let ranksep = g.graph().ranksep.unwrap_or(50.0);
+    let mut prev_y = 0.0;
+    for layer in build_layer_matrix(g) {
+        let max_height = layer
+            .iter()
+            .map(|v| g.node(v).unwrap().height)
+            .fold(0.0, f32::max);
+        for v in &layer {
+            g.node_mut(v).unwrap().y = prev_y + max_height / 2.0;
+        }
+        prev_y += max_height + ranksep;
+    }
+}
+
+type Conflicts = OrderedHashMap<String, OrderedHashMap<String, bool>>;
+type NeighborFn = Box<dyn Fn(&Graph<GraphConfig, GraphNode, GraphEdge>, &String) -> Vec<String>>;
+
+fn position_x(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) -> OrderedHashMap<String, f32> {
+    let layering = build_layer_matrix(g);
+    let mut conflicts = find_type_1_conflicts(g, &layering);
+    add_type_2_conflicts(g, &layering, &mut conflicts);
+
+    let mut xss: OrderedHashMap<String, OrderedHashMap<String, f32>> = OrderedHashMap::new();
+    for vert in ["u", "d"] {
+        let mut adjusted = layering.clone();
+        if vert == "d" {
+            adjusted.reverse();
+        }
+        for horiz in ["l", "r"] {
+            if horiz == "r" {
+                adjusted.iter_mut().for_each(|layer| layer.reverse());
+            }
+            let neighbor_fn: NeighborFn = if vert == "u" {
+                Box::new(|g, v| g.predecessors(v).unwrap_or_default())
+            } else {
+                Box::new(|g, v| g.successors(v).unwrap_or_default())
+            };
+            let (root, align) = vertical_alignment(g, &adjusted, &conflicts, neighbor_fn);
+            let mut xs =
+                position::bk::horizontal_compaction(g, &adjusted, &root, &align, horiz == "r");
+            if horiz == "r" {
+                xs.values_mut().for_each(|x| *x = -*x);
+            }
+            xss.insert(format!("{}{}", vert, horiz), xs);
+        }
+    }
+
+    let smallest_width = position::bk::find_smallest_width_alignment(g, &xss).clone();
+    align_coordinates(&mut xss, &smallest_width);
+    position::bk::balance(&xss, g.graph().align.clone())
+}
+
+// Marks segments that cross an inner segment, one between two dummies, so
+// that alignment favours keeping long edges straight.
+fn find_type_1_conflicts(
+    g: &Graph<GraphConfig, GraphNode, GraphEdge>,
+    layering: &[Vec<String>],
+) -> Conflicts {
+    let mut conflicts = Conflicts::new();
+    let order = |v: &String| g.node(v).and_then(|node| node.order).unwrap_or(0);
+    let is_dummy = |v: &String| g.node(v).is_some_and(|node| node.dummy.is_some());
+    for pair in layering.windows(2) {
+        let (prev_layer, layer) = (&pair[0], &pair[1]);
+        let (mut k0, mut scan_pos) = (0, 0);
+        for (i, v) in layer.iter().enumerate() {
+            let inner = if is_dummy(v) {
+                g.predecessors(v)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|u| is_dummy(u))
+            } else {
+                None
+            };
+            let k1 = inner.as_ref().map_or(prev_layer.len(), order);
+            if inner.is_none() && i + 1 != layer.len() {
+                continue;
+            }
+            for scan_node in &layer[scan_pos..=i] {
+                for u in g.predecessors(scan_node).unwrap_or_default() {
+                    let u_pos = order(&u);
+                    if (u_pos < k0 || k1 < u_pos) && !(is_dummy(&u) && is_dummy(scan_node)) {
+                        position::bk::add_conflict(&mut conflicts, &u, scan_node);
+                    }
+                }
+            }
+            scan_pos = i + 1;
+            k0 = k1;
+        }
+    }
+    conflicts
+}
+
+// Upstream's `find_type_2_conflicts`, which rescans the rest of the lower
+// rank for every node on it. What the rescans find is that a segment between
+// two dummies conflicts when its upper end lies left of the upper neighbour of
+// any group border met so far, up to the first border right of its lower end.
+// The same conflicts are added to `conflicts` here in one pass per rank.
+fn add_type_2_conflicts(
+    g: &Graph<GraphConfig, GraphNode, GraphEdge>,
+    layering: &[Vec<String>],
+    conflicts: &mut Conflicts,
+) {
+    let order = |v: &String| g.node(v).and_then(|node| node.order).unwrap_or(0) as i32;
+    let is_dummy = |v: &String| g.node(v).is_some_and(|node| node.dummy.is_some());
+    for south in layering.iter().skip(1) {
+        let mut borders: Vec<(usize, i32)> = vec![];
+        for (i, v) in south.iter().enumerate() {
+            if g.node(v).unwrap().dummy.as_deref() != Some("border") {
+                continue;
+            }
+            if let Some(u) = g.predecessors(v).unwrap_or_default().first() {
+                let north_pos = order(u).max(borders.last().map_or(-1, |&(_, pos)| pos));
+                borders.push((i, north_pos));
+            }
+        }
+
+        let mut next_border = 0;
+        for (i, v) in south.iter().enumerate() {
+            while next_border < borders.len() && borders[next_border].0 <= i {
+                next_border += 1;
+            }
+            let Some(&(_, north_pos)) = borders.get(next_border).or(borders.last()) else {
+                continue;
+            };
+            if !is_dummy(v) {
+                continue;
+            }
+            for u in g.predecessors(v).unwrap_or_default() {
+                if is_dummy(&u) && order(&u) < north_pos {
+                    position::bk::add_conflict(conflicts, &u, v);
+                }
+            }
+        }
+    }
+}
+
+// Upstream's `vertical_alignment`, which returns the alignment through
+// `OrderedHashMap::into_values`, quadratic in the number of nodes. The
+// alignment comes back here in the same order, that of the layering.
+fn vertical_alignment(
+    g: &Graph<GraphConfig, GraphNode, GraphEdge>,
+    layering: &[Vec<String>],
+    conflicts: &Conflicts,
+    neighbor_fn: NeighborFn,
+) -> (OrderedHashMap<String, String>, Vec<String>) {
+    let mut root = OrderedHashMap::new();
+    let mut align = HashMap::new();
+    let mut pos = HashMap::new();
+    for layer in layering {
+        for (order, v) in layer.iter().enumerate() {
+            root.insert(v.clone(), v.clone());
+            align.insert(v.clone(), v.clone());
+            pos.insert(v.clone(), order);
+        }
+    }
+
+    for layer in layering {
+        let mut prev_idx = -1;
+        for v in layer {
+            let mut ws = neighbor_fn(g, v);
+            if ws.is_empty() {
+                continue;
+            }
+            ws.sort_by_key(|w| pos[w]);
+            let mp = (ws.len() as f32 - 1.0) / 2.0;
+            for w in &ws[mp as usize..=mp.ceil() as usize] {
+                if &align[v] == v
+                    && prev_idx < pos[w] as i32
+                    && !position::bk::has_conflict(conflicts, v, w)
+                {
+                    align.insert(w.clone(), v.clone());
+                    let w_root = root.get(w).unwrap().clone();
+                    root.insert(v.clone(), w_root.clone());
+                    align.insert(v.clone(), w_root);
+                    prev_idx = pos[w] as i32;
+                }
+            }
+        }
+    }
+
+    let align = layering
+        .iter()
+        .flatten()
+        .map(|v| align[v].clone())
+        .collect();
+    (root, align)
+}
+
+// Shifts each alignment so that the left-biased ones start, and the
+// right-biased ones end, where the narrowest one does.
+fn align_coordinates(
+    xss: &mut OrderedHashMap<String, OrderedHashMap<String, f32>>,
+    align_to: &OrderedHashMap<String, f32>,
+) {
+    let align_to_min = align_to.values().copied().fold(f32::INFINITY, f32::min);
+    let align_to_max = align_to.values().copied().fold(f32::NEG_INFINITY, f32::max);
+    for (alignment, xs) in xss.iter_mut() {
+        let delta = if alignment.ends_with('l') {
+            align_to_min - xs.values().copied().fold(f32::INFINITY, f32::min)
+        } else {
+            align_to_max - xs.values().copied().fold(f32::NEG_INFINITY, f32::max)
+        };
+        xs.values_mut().for_each(|x| *x += delta);
+    }
+}
+
+// Moves each pinned node along its rank, after `position` has
+// placed everything, then pushes the nodes beside it further along as far as
+// they need to keep dagre's spacing, in the same order. Nothing moves between
+// ranks. Pins are given in the final coordinates of the unpinned layout, which
+// differ from dagre's working ones by the offset `translate_graph` applies.
+// Returns where the unpinned layout started along the ranks, for
+// `translate_graph` to keep.
+fn pin_along_ranks(
+    g: &mut Graph<GraphConfig, GraphNode, GraphEdge>,
+    pins: &HashMap<String, f32>,
+) -> Option<f32> {
+    if pins.is_empty() {
+        return None;
+    }
+    let config = g.graph();
+    let (nodesep, edgesep) = (
+        config.nodesep.unwrap_or(50.0),
+        config.edgesep.unwrap_or(20.0),
+    );
+    // `coordinate_system::adjust` swaps axes in horizontal layouts, so ranks
+    // always run along x here, and the margin that lines up with it is y's.
+    let horizontal = matches!(config.rankdir.as_deref(), Some("lr" | "rl"));
+    let margin = if horizontal {
+        config.marginy
+    } else {
+        config.marginx
+    }
+    .unwrap_or(0.0);
+    let layers = build_layer_matrix(g);
+    let origin = layers
+        .iter()
+        .flatten()
+        .map(|v| g.node(v).unwrap())
+        .map(|node| node.x - node.width / 2.0)
+        .fold(f32::INFINITY, f32::min);
+    // Half a node's width plus half the space dagre leaves beside it.
+    let half_extent = |node: &GraphNode| {
+        let sep = if node.dummy.is_some() {
+            edgesep
+        } else {
+            nodesep
+        };
+        (node.width + sep) / 2.0
+    };
+
+    for layer in layers {
+        if !layer.iter().any(|v| pins.contains_key(v)) {
+            continue;
+        }
+        let mut xs: Vec<f32> = layer.iter().map(|v| g.node(v).unwrap().x).collect();
+        let extents: Vec<f32> = layer
+            .iter()
+            .map(|v| half_extent(g.node(v).unwrap()))
+            .collect();
+        let pinned: Vec<bool> = layer.iter().map(|v| pins.contains_key(v)).collect();
+        for (i, v) in layer.iter().enumerate() {
+            if let Some(pin) = pins.get(v) {
+                xs[i] = pin + origin - margin;
+            }
+        }
+        for i in 1..layer.len() {
+            let min_x = xs[i - 1] + extents[i - 1] + extents[i];
+            if !pinned[i] && xs[i] < min_x {
+                xs[i] = min_x;
+            }
+        }
+        for i in (0..layer.len().saturating_sub(1)).rev() {
+            let max_x = xs[i + 1] - extents[i + 1] - extents[i];
+            if !pinned[i] && xs[i] > max_x {
+                xs[i] = max_x;
+            }
+        }
+        for (v, x) in layer.iter().zip(xs) {
+            g.node_mut(v).unwrap().x = x;
+        }
+    }
+    Some(origin)
+}
+
+// What upstream's network simplex ranks with: longest-path ranks tightened
+// into a feasible tree. Its pivots never move a node (`update_ranks` looks for
+// the tree's root among the wrong graph's nodes and finds none), so they're
+// skipped; on nesting graphs they can also cycle forever. `shorten_heavy_edges`
+// makes up part of what they would find, so edge weights still count.
+fn rank_feasible_tree(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    simplify_ref(g);
+    rank::util::longest_path(g);
+    rank::feasible_tree::feasible_tree(g);
+    shorten_heavy_edges(g);
+}
+
+// A heuristic for what network simplex does, which is minimize the total
+// weighted length of the edges. A node's share of that total is linear in its
+// rank, so each node in turn moves as far as its edges' minlens allow towards
+// the side whose edges weigh more, and stays put on a tie. Every move shortens
+// the total, so the passes end once no node moves. Nodes only move one at a
+// time, though, so where the total would only shrink with several nodes moving
+// together, which network simplex's pivots find, this stops short of the
+// minimum.
+fn shorten_heavy_edges(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    let rank_of = |g: &Graph<GraphConfig, GraphNode, GraphEdge>, v: &String| {
+        g.node(v).and_then(|node| node.rank).unwrap_or(0)
+    };
+    let label = |g: &Graph<GraphConfig, GraphNode, GraphEdge>, e: &graphlib_rust::Edge| {
+        let edge = g.edge_with_obj(e);
+        (
+            edge.and_then(|edge| edge.weight).unwrap_or(1.0),
+            edge.and_then(|edge| edge.minlen).unwrap_or(1.0).round() as i32,
+        )
+    };
+
+    let mut moved = true;
+    while moved {
+        moved = false;
+        for v in g.nodes() {
+            let (mut pull, mut highest, mut lowest) = (0.0, None::<i32>, None::<i32>);
+            for e in g.in_edges(&v, None).unwrap_or_default() {
+                let (weight, minlen) = label(g, &e);
+                pull -= weight;
+                let bound = rank_of(g, &e.v) + minlen;
+                highest = Some(highest.map_or(bound, |highest| highest.max(bound)));
+            }
+            for e in g.out_edges(&v, None).unwrap_or_default() {
+                let (weight, minlen) = label(g, &e);
+                pull += weight;
+                let bound = rank_of(g, &e.w) - minlen;
+                lowest = Some(lowest.map_or(bound, |lowest| lowest.min(bound)));
+            }
+            let target = if pull > 0.0 {
+                lowest
+            } else if pull < 0.0 {
+                highest
+            } else {
+                None
+            };
+            if let Some(target) = target.filter(|target| *target != rank_of(g, &v)) {
+                g.node_mut(&v).unwrap().rank = Some(target);
+                moved = true;
+            }
+        }
+    }
+}
+
+// Moves each overridden node onto its rank, then pushes the other nodes along
+// until every edge spans at least its minlen again. Overrides are relative:
+// the node with the lowest override stays on its dagre rank and the rest are
+// placed from there, one override step per rank of an ordinary edge. A node
+// whose override can't be kept, because a path from another overridden node
+// needs more ranks than the two overrides leave, is a conflict.
+fn pin_ranks(
+    g: &mut Graph<GraphConfig, GraphNode, GraphEdge>,
+    rank_overrides: &HashMap<String, i32>,
+) -> Result<(), LayoutError> {
+    let pinned: Vec<(&String, i32)> = rank_overrides
+        .iter()
+        .filter(|(v, _)| g.has_node(v))
+        .map(|(v, rank)| (v, *rank))
+        .collect();
+    let Some(&(lowest, lowest_override)) = pinned.iter().min_by_key(|(v, rank)| (*rank, *v)) else {
+        return Ok(());
+    };
+    // dagre doubles minlen to make room for edge labels, and nesting_graph
+    // multiplies it again by node_rank_factor.
+    let unit = 2 * g.graph().node_rank_factor.unwrap_or(1.0) as i32;
+    let rank_of = |g: &Graph<GraphConfig, GraphNode, GraphEdge>, v: &String| {
+        g.node(v).and_then(|node| node.rank).unwrap_or(0)
+    };
+    let anchor = rank_of(g, lowest);
+    let targets: HashMap<&String, i32> = pinned
+        .into_iter()
+        .map(|(v, rank)| (v, anchor + (rank - lowest_override) * unit))
+        .collect();
+    let minlen = |g: &Graph<GraphConfig, GraphNode, GraphEdge>, e: &graphlib_rust::Edge| {
+        g.edge_with_obj(e)
+            .and_then(|edge| edge.minlen)
+            .unwrap_or(1.0)
+            .round() as i32
+    };
+    let order = algo::topsort(g).unwrap_or_default();
+
+    // Forward pass: nothing sits above the ranks its predecessors push it to.
+    for v in &order {
+        let lower = g
+            .in_edges(v, None)
+            .unwrap_or_default()
+            .iter()
+            .map(|e| rank_of(g, &e.v) + minlen(g, e))
+            .max();
+        let rank = match (targets.get(v), lower) {
+            (Some(&target), Some(lower)) if lower > target => {
+                return Err(LayoutError::RankConflict(v.clone()))
+            }
+            (Some(&target), _) => target,
+            (None, lower) => lower.map_or(rank_of(g, v), |lower| lower.max(rank_of(g, v))),
+        };
+        g.node_mut(v).unwrap().rank = Some(rank);
+    }
+    // Backward pass: pull predecessors of nodes that moved up along with them.
+    for v in order.iter().rev() {
+        let upper = g
+            .out_edges(v, None)
+            .unwrap_or_default()
+            .iter()
+            .map(|e| rank_of(g, &e.w) - minlen(g, e))
+            .min();
+        match upper {
+            Some(upper) if upper < rank_of(g, v) => {
+                if targets.contains_key(v) {
+                    return Err(LayoutError::RankConflict(v.clone()));
+                }
+                g.node_mut(v).unwrap().rank = Some(upper);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Adds a dummy node halfway between the ends of each labelled edge, so that the
+// label gets a rank of its own.
+fn inject_edge_label_proxies(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    for e in g.edges() {
+        let Some(edge) = g.edge_with_obj(&e) else {
+            continue;
+        };
+        if !has_label(edge) {
+            continue;
+        }
+        let rank_of = |v: &String| g.node(v).and_then(|node| node.rank).unwrap_or(0);
+        let (v_rank, w_rank) = (rank_of(&e.v), rank_of(&e.w));
+        let label = GraphNode {
+            rank: Some((w_rank - v_rank) / 2 + v_rank),
+            e: Some(e.clone()),
+            ..Default::default()
+        };
+        add_dummy_node(g, "edge-proxy".to_string(), label, "_ep".to_string());
+    }
+}
+
+// Re-tags the dummy on each edge's label rank, which `add_dummy_node` overwrote.
+fn tag_edge_label_dummies(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    for v in g.nodes() {
+        let node = g.node_mut(&v).unwrap();
+        let label_rank = node.edge_label.as_ref().and_then(|edge| edge.label_rank);
+        if node.dummy.as_deref() == Some("edge") && label_rank.is_some() && node.rank == label_rank
+        {
+            node.dummy = Some("edge-label".to_string());
+        }
+    }
+}
+
+// dagre's `parentDummyChains`: moves the dummies along each long edge into the
+// groups the edge passes through on each rank, climbing from its tail to the
+// lowest common ancestor of both ends and back down to its head.
+fn parent_dummy_chains(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    let postorder_nums = postorder(g);
+    for start in g.graph().dummy_chains.clone().unwrap_or_default() {
+        let Some(edge_obj) = g.node(&start).and_then(|node| node.edge_obj.clone()) else {
+            continue;
+        };
+        let (path, lca) = find_path(g, &postorder_nums, &edge_obj.v, &edge_obj.w);
+        let rank_bound = |g: &Graph<GraphConfig, GraphNode, GraphEdge>,
+                          v: &Option<String>,
+                          bound: fn(&GraphNode) -> Option<i32>| {
+            v.as_ref().and_then(|v| g.node(v)).and_then(bound)
+        };
+        let mut path_idx = 0;
+        let mut ascending = true;
+        let mut v = start;
+        while v != edge_obj.w {
+            let rank = g.node(&v).and_then(|node| node.rank).unwrap_or(0);
+            if ascending {
+                while path[path_idx] != lca
+                    && rank_bound(g, &path[path_idx], |node| node.max_rank)
+                        .is_some_and(|max_rank| max_rank < rank)
+                {
+                    path_idx += 1;
+                }
+                if path[path_idx] == lca {
+                    ascending = false;
+                }
+            }
+            if !ascending {
+                while path_idx < path.len() - 1
+                    && rank_bound(g, &path[path_idx + 1], |node| node.min_rank)
+                        .is_some_and(|min_rank| min_rank <= rank)
+                {
+                    path_idx += 1;
+                }
+            }
+            let _ = g.set_parent(&v, path[path_idx].clone());
+            let Some(next) = g.successors(&v).and_then(|vs| vs.into_iter().next()) else {
+                break;
+            };
+            v = next;
+        }
+    }
+}
+
+// The groups on the way from `v` up to the lowest common ancestor of `v` and
+// `w`, then down to `w`, along with that ancestor. `None` is the root.
+fn find_path(
+    g: &Graph<GraphConfig, GraphNode, GraphEdge>,
+    postorder_nums: &HashMap<String, (usize, usize)>,
+    v: &String,
+    w: &String,
+) -> (Vec<Option<String>>, Option<String>) {
+    let (v_low, v_lim) = postorder_nums[v];
+    let (w_low, w_lim) = postorder_nums[w];
+    let (low, lim) = (v_low.min(w_low), v_lim.max(w_lim));
+
+    let mut path = vec![];
+    let mut parent = Some(v.clone());
+    loop {
+        parent = parent.and_then(|p| g.parent(&p).cloned());
+        path.push(parent.clone());
+        match &parent {
+            Some(p) if postorder_nums[p].0 > low || lim > postorder_nums[p].1 => {}
+            _ => break,
+        }
+    }
+    let lca = parent;
+
+    let mut w_path = vec![];
+    let mut parent = g.parent(w).cloned();
+    while parent != lca {
+        let Some(p) = parent else {
+            break;
+        };
+        parent = g.parent(&p).cloned();
+        w_path.push(Some(p));
+    }
+    path.extend(w_path.into_iter().rev());
+    (path, lca)
+}
+
+// Each node's `(low, lim)`: its position in a postorder walk of the group
+// tree, and the lowest position among its descendants.
+fn postorder(g: &Graph<GraphConfig, GraphNode, GraphEdge>) -> HashMap<String, (usize, usize)> {
+    fn dfs(
+        g: &Graph<GraphConfig, GraphNode, GraphEdge>,
+        v: &String,
+        lim: &mut usize,
+        result: &mut HashMap<String, (usize, usize)>,
+    ) {
+        let low = *lim;
+        for child in g.children(v) {
+            dfs(g, &child, lim, result);
+        }
+        result.insert(v.clone(), (low, *lim));
+        *lim += 1;
+    }
+
+    let mut result = HashMap::new();
+    let mut lim = 0;
+    for v in g.children(&GRAPH_NODE.to_string()) {
+        dfs(g, &v, &mut lim, &mut result);
+    }
+    result
+}
+
+// dagre's `addBorderSegments`: a chain of border nodes down each side of every
+// group, one per rank it spans.
+fn add_border_segments(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    fn dfs(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>, v: &String) {
+        for child in g.children(v) {
+            dfs(g, &child);
+        }
+        let Some((min_rank, max_rank)) = g
+            .node(v)
+            .and_then(|node| Some((node.min_rank?, node.max_rank?)))
+        else {
+            return;
+        };
+        let node = g.node_mut(v).unwrap();
+        node.border_left = Some(OrderedHashMap::new());
+        node.border_right = Some(OrderedHashMap::new());
+        for rank in min_rank..=max_rank {
+            add_border_node(g, v, BorderTypeName::BorderLeft, rank);
+            add_border_node(g, v, BorderTypeName::BorderRight, rank);
+        }
+    }
+
+    for v in g.children(&GRAPH_NODE.to_string()) {
+        dfs(g, &v);
+    }
+}
+
+fn add_border_node(
+    g: &mut Graph<GraphConfig, GraphNode, GraphEdge>,
+    group: &String,
+    side: BorderTypeName,
+    rank: i32,
+) {
+    let prefix = match side {
+        BorderTypeName::BorderLeft => "_bl",
+        BorderTypeName::BorderRight => "_br",
+    };
+    let label = GraphNode {
+        rank: Some(rank),
+        border_type: Some(side.clone()),
+        ..Default::default()
+    };
+    let curr = add_dummy_node(g, "border".to_string(), label, prefix.to_string());
+
+    let group_node = g.node_mut(group).unwrap();
+    let border = match side {
+        BorderTypeName::BorderLeft => group_node.border_left.as_mut(),
+        BorderTypeName::BorderRight => group_node.border_right.as_mut(),
+    }
+    .unwrap();
+    let prev = border.get(&(rank - 1)).cloned();
+    border.insert(rank, curr.clone());
+
+    if let Some(prev) = prev {
+        let edge = GraphEdge {
+            weight: Some(1.0),
+            ..Default::default()
+        };
+        let _ = g.set_edge(&prev, &curr, Some(edge), None);
+    }
+    let _ = g.set_parent(&curr, Some(group.clone()));
+}
+
+// Upstream's `insert_self_edges`, over a layer matrix without the groups.
+fn insert_self_edges(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    for layer in build_layer_matrix(g) {
+        let mut order_shift = 0;
+        for (i, v) in layer.iter().enumerate() {
+            let node = g.node_mut(v).unwrap();
+            node.order = Some(i + order_shift);
+            let rank = node.rank;
+            for (e, label) in node.self_edges.clone() {
+                order_shift += 1;
+                let dummy = GraphNode {
+                    width: label.width.unwrap_or(0.0),
+                    height: label.height.unwrap_or(0.0),
+                    rank,
+                    order: Some(i + order_shift),
+                    e: Some(e),
+                    label: Some(label),
+                    ..Default::default()
+                };
+                add_dummy_node(g, "selfedge".to_string(), dummy, "_se".to_string());
+            }
+        }
+    }
+}
+
+// dagre's `removeBorderNodes`: sizes each group to its border nodes, then
+// drops them. Upstream centres groups vertically on their last left border
+// node instead of between the top and bottom ones.
+fn remove_border_nodes(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    fn last(chain: Option<&OrderedHashMap<i32, String>>) -> Option<&String> {
+        chain.and_then(|chain| chain.keys().max().and_then(|rank| chain.get(rank)))
+    }
+    for v in g.nodes() {
+        if g.children(&v).is_empty() {
+            continue;
+        }
+        let node = g.node(&v).unwrap();
+        let border = |id: Option<&String>| id.and_then(|id| g.node(id)).cloned();
+        let (Some(t), Some(b), Some(l), Some(r)) = (
+            border(node.border_top.as_ref()),
+            border(node.border_bottom.as_ref()),
+            border(last(node.border_left.as_ref())),
+            border(last(node.border_right.as_ref())),
+        ) else {
+            continue;
+        };
+        let node = g.node_mut(&v).unwrap();
+        node.width = (r.x - l.x).abs();
+        node.height = (b.y - t.y).abs();
+        node.x = l.x + node.width / 2.0;
+        node.y = t.y + node.height / 2.0;
+    }
+
+    for v in g.nodes() {
+        if g.node(&v).unwrap().dummy.as_deref() == Some("border") {
+            g.remove_node(&v);
+        }
+    }
+}
+
+// dagre's `normalize.undo`: turns each chain of dummies back into the edge it
+// stands for, with a point per dummy. Upstream removes the dummies one at a
+// time, and graphlib_rust's `remove_node` scans every node and edge, so this
+// copies the graph once without them instead.
+fn undo_normalize(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>) {
+    let Some(dummy_chains) = g.graph().dummy_chains.clone() else {
+        return;
+    };
+    let mut dummies = HashSet::new();
+    let mut restored = vec![];
+    for start in &dummy_chains {
+        let Some(mut node) = g.node(start) else {
+            continue;
+        };
+        let mut label = node.edge_label.clone().unwrap_or_default();
+        let edge_obj = node.edge_obj.clone().unwrap();
+        let mut v = start.clone();
+        while node.dummy.is_some() {
+            label
+                .points
+                .get_or_insert_with(Vec::new)
+                .push(GraphEdgePoint {
+                    x: node.x,
+                    y: node.y,
+                });
+            if node.dummy.as_deref() == Some("edge-label") {
+                label.x = node.x;
+                label.y = node.y;
+                label.width = Some(node.width);
+                label.height = Some(node.height);
+            }
+            let w = g.successors(&v).unwrap_or_default().into_iter().next();
+            dummies.insert(v);
+            v = w.unwrap_or_default();
+            node = g.node(&v).unwrap();
+        }
+        restored.push((edge_obj, label));
+    }
+
+    let kept: HashSet<String> = g
+        .nodes()
+        .into_iter()
+        .filter(|v| !dummies.contains(v))
+        .collect();
+    *g = g.subgraph(&kept);
+    for (edge_obj, label) in restored {
+        let _ = g.set_edge_with_obj(&edge_obj, Some(label));
+    }
+}
+
+// Shifts everything so the top-left corner sits at the graph margins, and
+// records the resulting graph size. With pins, the layout keeps the
+// `unpinned_origin` along its ranks, so that moving the outermost node inwards
+// doesn't shift everything else, unless something now lies further out.
+fn translate_graph(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>, unpinned_origin: Option<f32>) {
+    let (mut min_x, mut max_x) = (f32::INFINITY, 0.0_f32);
+    let (mut min_y, mut max_y) = (f32::INFINITY, 0.0_f32);
+    let mut extend = |x: f32, y: f32, width: f32, height: f32| {
+        min_x = min_x.min(x - width / 2.0);
+        max_x = max_x.max(x + width / 2.0);
+        min_y = min_y.min(y - height / 2.0);
+        max_y = max_y.max(y + height / 2.0);
+    };
+    for v in g.nodes() {
+        let node = g.node(&v).unwrap();
+        extend(node.x, node.y, node.width, node.height);
+    }
+    for e in g.edges() {
+        let edge = g.edge_with_obj(&e).unwrap();
+        if has_label(edge) {
+            extend(
+                edge.x,
+                edge.y,
+                edge.width.unwrap_or(0.0),
+                edge.height.unwrap_or(0.0),
+            );
+        }
+    }
+
+    if let Some(origin) = unpinned_origin {
+        // `coordinate_system::undo` has put the ranks back across y in
+        // horizontal layouts, leaving positions along them unchanged.
+        match g.graph().rankdir.as_deref() {
+            Some("lr" | "rl") => min_y = min_y.min(origin),
+            _ => min_x = min_x.min(origin),
+        }
+    }
+
+    let mut graph_label = g.graph().clone();
+    let margin_x = graph_label.marginx.unwrap_or(0.0);
+    let margin_y = graph_label.marginy.unwrap_or(0.0);
+    min_x -= margin_x;
+    min_y -= margin_y;
+
+    for v in g.nodes() {
+        let node = g.node_mut(&v).unwrap();
+        node.x -= min_x;
+        node.y -= min_y;
+    }
+    for e in g.edges() {
+        let edge = g.edge_mut_with_obj(&e).unwrap();
+        for p in edge.points.iter_mut().flatten() {
+            p.x -= min_x;
+            p.y -= min_y;
+        }
+        if has_label(edge) {
+            edge.x -= min_x;
+            edge.y -= min_y;
+        }
+    }
+
+    graph_label.width = max_x - min_x + margin_x;
+    graph_label.height = max_y - min_y + margin_y;
+    g.set_graph(graph_label);
+}
+
+fn has_label(edge: &GraphEdge) -> bool {
+    edge.width.unwrap_or(0.0) > 0.0 && edge.height.unwrap_or(0.0) > 0.0
+}
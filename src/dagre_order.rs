@@ -0,0 +1,571 @@
+//! dagre's `order` phase, ported from its JavaScript for `dagre_layout`.
+//!
+//! dagre_rust's port doesn't reorder anything: `sort` drops every entry,
+//! `resolve_conflicts` never carries barycenters over, layer graphs hold copies
+//! of the node labels so no order found by a sweep reaches the graph, and the
+//! rank 0 layer graph takes in every node of the graph. Layouts were left with
+//! the initial DFS order, which also splits groups apart.
+//!
+//! dagre's layer graphs share node labels with the graph being laid out. Here
+//! they only hold the hierarchy, edge weights and group borders, and orders
+//! are read from and written to the graph directly.
+
+use dagre_rust::{GraphConfig, GraphEdge, GraphNode};
+use graphlib_rust::{Graph, GraphOption};
+use ordered_hashmap::OrderedHashMap;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet};
+
+type DagreGraph = Graph<GraphConfig, GraphNode, GraphEdge>;
+
+/// Orders the nodes on each rank to reduce edge crossings, keeping each group's
+/// members together between its borders. Sets `order` on every node that has
+/// a rank.
+pub(crate) fn order(g: &mut DagreGraph) {
+    let max_rank = max_rank(g);
+    let members = rank_members(g, max_rank);
+    let layer_graph =
+        |rank: i32, relationship| LayerGraph::new(g, &members[rank as usize], rank, relationship);
+    let down_layer_graphs: Vec<LayerGraph> = (1..=max_rank)
+        .map(|rank| layer_graph(rank, Relationship::InEdges))
+        .collect();
+    let up_layer_graphs: Vec<LayerGraph> = (0..max_rank)
+        .rev()
+        .map(|rank| layer_graph(rank, Relationship::OutEdges))
+        .collect();
+
+    assign_order(g, &init_order(g));
+
+    let mut best = vec![];
+    let mut best_cc = usize::MAX;
+    let mut last_best = 0;
+    let mut i = 0;
+    while last_best < 4 {
+        let layer_graphs = if i % 2 == 1 {
+            &down_layer_graphs
+        } else {
+            &up_layer_graphs
+        };
+        sweep_layer_graphs(g, layer_graphs, i % 4 >= 2);
+
+        let layering = build_layer_matrix(g);
+        let cc = cross_count(g, &layering);
+        if cc < best_cc {
+            last_best = 0;
+            best = layering;
+            best_cc = cc;
+        }
+        last_best += 1;
+        i += 1;
+    }
+
+    assign_order(g, &best);
+}
+
+/// The ids on each rank, sorted by order. Groups span ranks rather than
+/// sitting on one, so they're left out.
+pub(crate) fn build_layer_matrix(g: &DagreGraph) -> Vec<Vec<String>> {
+    let mut layering = vec![vec![]; max_rank(g) as usize + 1];
+    for v in g.nodes() {
+        let node = g.node(&v).unwrap();
+        if let Some(rank) = node.rank {
+            layering[rank as usize].push((node.order.unwrap_or(0), v));
+        }
+    }
+    layering
+        .into_iter()
+        .map(|mut layer| {
+            layer.sort();
+            layer.into_iter().map(|(_, v)| v).collect()
+        })
+        .collect()
+}
+
+pub(crate) fn max_rank(g: &DagreGraph) -> i32 {
+    g.nodes()
+        .iter()
+        .filter_map(|v| g.node(v).and_then(|node| node.rank))
+        .max()
+        .unwrap_or(0)
+}
+
+// The nodes on each rank, and the groups spanning it, in graph order.
+fn rank_members(g: &DagreGraph, max_rank: i32) -> Vec<Vec<String>> {
+    let mut members = vec![vec![]; max_rank as usize + 1];
+    for v in g.nodes() {
+        let node = g.node(&v).unwrap();
+        let spans_rank = |rank: i32| match (node.min_rank, node.max_rank) {
+            (Some(min_rank), Some(max_rank)) => min_rank <= rank && rank <= max_rank,
+            _ => false,
+        };
+        for (rank, layer) in members.iter_mut().enumerate() {
+            if node.rank == Some(rank as i32) || spans_rank(rank as i32) {
+                layer.push(v.clone());
+            }
+        }
+    }
+    members
+}
+
+fn assign_order(g: &mut DagreGraph, layering: &[Vec<String>]) {
+    for layer in layering {
+        for (i, v) in layer.iter().enumerate() {
+            g.node_mut(v).unwrap().order = Some(i);
+        }
+    }
+}
+
+// A first ordering from a DFS that starts at the lowest ranks, placing nodes on
+// their rank as they're reached.
+fn init_order(g: &DagreGraph) -> Vec<Vec<String>> {
+    let rank_of = |v: &String| g.node(v).and_then(|node| node.rank).unwrap_or(0);
+    let mut simple_nodes: Vec<String> = g
+        .nodes()
+        .into_iter()
+        .filter(|v| g.children(v).is_empty())
+        .collect();
+    let max_rank = simple_nodes.iter().map(rank_of).max().unwrap_or(0);
+    simple_nodes.sort_by_key(rank_of);
+
+    let mut layers = vec![vec![]; max_rank as usize + 1];
+    let mut visited = HashSet::new();
+    for start in simple_nodes {
+        let mut stack = vec![start];
+        while let Some(v) = stack.pop() {
+            if !visited.insert(v.clone()) {
+                continue;
+            }
+            layers[rank_of(&v) as usize].push(v.clone());
+            let mut successors = g.successors(&v).unwrap_or_default();
+            successors.reverse();
+            stack.extend(successors);
+        }
+    }
+    layers
+}
+
+fn sweep_layer_graphs(g: &mut DagreGraph, layer_graphs: &[LayerGraph], bias_right: bool) {
+    let mut constraints = Constraints::default();
+    for lg in layer_graphs {
+        let sorted = sort_subgraph(g, lg, &lg.root, &constraints, bias_right);
+        for (i, v) in sorted.vs.iter().enumerate() {
+            g.node_mut(v).unwrap().order = Some(i);
+        }
+        constraints.add_subgraph_constraints(&lg.graph, &sorted.vs);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Relationship {
+    InEdges,
+    OutEdges,
+}
+
+// The nodes on one rank, nested in their groups under `root`, with an edge
+// into each from its neighbours on the adjacent rank. Nodes from the adjacent
+// rank sit outside `root`, so they aren't moved.
+struct LayerGraph {
+    root: String,
+    graph: Graph<(), LayerNode, f64>,
+}
+
+// The border nodes of a group on this layer's rank.
+#[derive(Debug, Clone, Default)]
+struct LayerNode {
+    borders: Option<(String, String)>,
+}
+
+impl LayerGraph {
+    fn new(g: &DagreGraph, members: &[String], rank: i32, relationship: Relationship) -> Self {
+        let mut root = "_root".to_string();
+        let mut suffix = 0;
+        while g.has_node(&root) {
+            suffix += 1;
+            root = format!("_root{}", suffix);
+        }
+        let mut graph = Graph::new(Some(GraphOption {
+            directed: Some(true),
+            multigraph: Some(false),
+            compound: Some(true),
+        }));
+
+        for v in members {
+            let node = g.node(v).unwrap();
+            graph.set_node(v.clone(), None);
+            let parent = g.parent(v).cloned().unwrap_or_else(|| root.clone());
+            let _ = graph.set_parent(v, Some(parent));
+
+            let edges = match relationship {
+                Relationship::InEdges => g.in_edges(v, None),
+                Relationship::OutEdges => g.out_edges(v, None),
+            };
+            for e in edges.unwrap_or_default() {
+                let u = if e.v == *v { &e.w } else { &e.v };
+                let weight = g
+                    .edge_with_obj(&e)
+                    .and_then(|edge| edge.weight)
+                    .unwrap_or(0.0) as f64;
+                let existing = graph.edge(u, v, None).copied().unwrap_or(0.0);
+                let _ = graph.set_edge(u, v, Some(existing + weight), None);
+            }
+
+            if node.min_rank.is_some() {
+                let border = |chain: &Option<OrderedHashMap<i32, String>>| {
+                    chain.as_ref()?.get(&rank).cloned()
+                };
+                let borders = border(&node.border_left).zip(border(&node.border_right));
+                graph.set_node(v.clone(), Some(LayerNode { borders }));
+            }
+        }
+
+        LayerGraph { root, graph }
+    }
+}
+
+// Pairs of sibling groups whose order an earlier layer settled, so that later
+// layers keep them the same way round.
+#[derive(Default)]
+struct Constraints {
+    edges: Vec<(String, String)>,
+}
+
+impl Constraints {
+    fn add_subgraph_constraints(&mut self, lg: &Graph<(), LayerNode, f64>, vs: &[String]) {
+        let mut prev: HashMap<String, String> = HashMap::new();
+        let mut root_prev: Option<String> = None;
+        for v in vs {
+            let mut child = lg.parent(v).cloned();
+            while let Some(c) = child {
+                let parent = lg.parent(&c).cloned();
+                let prev_child = match &parent {
+                    Some(parent) => prev.insert(parent.clone(), c.clone()),
+                    None => root_prev.replace(c.clone()),
+                };
+                if let Some(prev_child) = prev_child {
+                    if prev_child != c {
+                        let edge = (prev_child, c);
+                        if !self.edges.contains(&edge) {
+                            self.edges.push(edge);
+                        }
+                        break;
+                    }
+                }
+                child = parent;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct SubgraphResult {
+    vs: Vec<String>,
+    barycenter: Option<f64>,
+    weight: f64,
+}
+
+fn sort_subgraph(
+    g: &DagreGraph,
+    lg: &LayerGraph,
+    v: &String,
+    constraints: &Constraints,
+    bias_right: bool,
+) -> SubgraphResult {
+    let borders = lg.graph.node(v).and_then(|node| node.borders.clone());
+    let movable: Vec<String> = lg
+        .graph
+        .children(v)
+        .into_iter()
+        .filter(|w| borders.as_ref().is_none_or(|(bl, br)| w != bl && w != br))
+        .collect();
+
+    let mut subgraphs: HashMap<String, SubgraphResult> = HashMap::new();
+    let mut barycenters = barycenter(g, &lg.graph, &movable);
+    for entry in barycenters.iter_mut() {
+        if lg.graph.children(&entry.v).is_empty() {
+            continue;
+        }
+        let result = sort_subgraph(g, lg, &entry.v, constraints, bias_right);
+        if let Some(barycenter) = result.barycenter {
+            entry.merge(barycenter, result.weight);
+        }
+        subgraphs.insert(entry.v.clone(), result);
+    }
+
+    let mut entries = resolve_conflicts(&barycenters, constraints);
+    for entry in entries.iter_mut() {
+        entry.vs = entry
+            .vs
+            .iter()
+            .flat_map(|v| match subgraphs.get(v) {
+                Some(subgraph) => subgraph.vs.clone(),
+                None => vec![v.clone()],
+            })
+            .collect();
+    }
+
+    let mut result = sort(entries, bias_right);
+    if let Some((bl, br)) = borders {
+        result.vs.insert(0, bl.clone());
+        result.vs.push(br.clone());
+        let pred_order = |border: &String| {
+            let pred = lg.graph.predecessors(border)?.into_iter().next()?;
+            Some(g.node(&pred)?.order.unwrap_or(0) as f64)
+        };
+        if let (Some(bl_order), Some(br_order)) = (pred_order(&bl), pred_order(&br)) {
+            let (barycenter, weight) = (result.barycenter.unwrap_or(0.0), result.weight);
+            result.barycenter = Some((barycenter * weight + bl_order + br_order) / (weight + 2.0));
+            result.weight = weight + 2.0;
+        }
+    }
+    result
+}
+
+#[derive(Debug, Clone)]
+struct Barycenter {
+    v: String,
+    barycenter: Option<f64>,
+    weight: f64,
+}
+
+impl Barycenter {
+    fn merge(&mut self, barycenter: f64, weight: f64) {
+        self.barycenter = Some(match self.barycenter {
+            Some(own) => (own * self.weight + barycenter * weight) / (self.weight + weight),
+            None => barycenter,
+        });
+        self.weight = match self.barycenter {
+            Some(_) if self.weight > 0.0 => self.weight + weight,
+            _ => weight,
+        };
+    }
+}
+
+// The weighted mean order of each movable node's neighbours on the adjacent
+// rank.
+fn barycenter(
+    g: &DagreGraph,
+    lg: &Graph<(), LayerNode, f64>,
+    movable: &[String],
+) -> Vec<Barycenter> {
+    movable
+        .iter()
+        .map(|v| {
+            let in_edges = lg.in_edges(v, None).unwrap_or_default();
+            if in_edges.is_empty() {
+                return Barycenter {
+                    v: v.clone(),
+                    barycenter: None,
+                    weight: 0.0,
+                };
+            }
+            let (mut sum, mut weight) = (0.0, 0.0);
+            for e in &in_edges {
+                let edge_weight = lg.edge_with_obj(e).copied().unwrap_or(0.0);
+                let order = g.node(&e.v).and_then(|node| node.order).unwrap_or(0);
+                sum += edge_weight * order as f64;
+                weight += edge_weight;
+            }
+            Barycenter {
+                v: v.clone(),
+                barycenter: Some(sum / weight),
+                weight,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct ConflictEntry {
+    vs: Vec<String>,
+    i: usize,
+    barycenter: Option<f64>,
+    weight: Option<f64>,
+    indegree: usize,
+    ins: Vec<usize>,
+    outs: Vec<usize>,
+    merged: bool,
+}
+
+// Coalesces entries whose barycenters would put them against an earlier
+// layer's constraints, so sorting can't separate them the wrong way round.
+fn resolve_conflicts(barycenters: &[Barycenter], constraints: &Constraints) -> Vec<ConflictEntry> {
+    let mut entries: Vec<ConflictEntry> = barycenters
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| ConflictEntry {
+            vs: vec![entry.v.clone()],
+            i,
+            barycenter: entry.barycenter,
+            weight: entry.barycenter.map(|_| entry.weight),
+            indegree: 0,
+            ins: vec![],
+            outs: vec![],
+            merged: false,
+        })
+        .collect();
+    let index: HashMap<&String, usize> = barycenters
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (&entry.v, i))
+        .collect();
+    for (v, w) in &constraints.edges {
+        if let (Some(&v), Some(&w)) = (index.get(v), index.get(w)) {
+            entries[w].indegree += 1;
+            entries[v].outs.push(w);
+        }
+    }
+
+    let mut source_set: Vec<usize> = (0..entries.len())
+        .filter(|&i| entries[i].indegree == 0)
+        .collect();
+    let mut visited = vec![];
+    while let Some(v) = source_set.pop() {
+        visited.push(v);
+        for u in entries[v].ins.clone().into_iter().rev() {
+            if entries[u].merged {
+                continue;
+            }
+            let (u_bc, v_bc) = (entries[u].barycenter, entries[v].barycenter);
+            let in_order = matches!((u_bc, v_bc), (Some(u_bc), Some(v_bc)) if u_bc < v_bc);
+            if !in_order {
+                merge_entries(&mut entries, v, u);
+            }
+        }
+        for w in entries[v].outs.clone() {
+            entries[w].ins.push(v);
+            entries[w].indegree -= 1;
+            if entries[w].indegree == 0 {
+                source_set.push(w);
+            }
+        }
+    }
+
+    visited
+        .into_iter()
+        .map(|i| entries[i].clone())
+        .filter(|entry| !entry.merged)
+        .collect()
+}
+
+fn merge_entries(entries: &mut [ConflictEntry], target: usize, source: usize) {
+    let (mut sum, mut weight) = (0.0, 0.0);
+    for entry in [&entries[target], &entries[source]] {
+        if let Some(entry_weight) = entry.weight.filter(|&weight| weight != 0.0) {
+            sum += entry.barycenter.unwrap_or(0.0) * entry_weight;
+            weight += entry_weight;
+        }
+    }
+    let mut vs = entries[source].vs.clone();
+    vs.append(&mut entries[target].vs);
+    let i = entries[source].i.min(entries[target].i);
+
+    let target = &mut entries[target];
+    target.vs = vs;
+    target.barycenter = Some(sum / weight);
+    target.weight = Some(weight);
+    target.i = i;
+    entries[source].merged = true;
+}
+
+// Sorts entries with a barycenter by it, and slots those without one back in
+// at their original index.
+fn sort(entries: Vec<ConflictEntry>, bias_right: bool) -> SubgraphResult {
+    let (mut sortable, mut unsortable): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .partition(|entry| entry.barycenter.is_some());
+    sortable.sort_by(|a, b| {
+        let (a_bc, b_bc) = (a.barycenter.unwrap(), b.barycenter.unwrap());
+        match a_bc.partial_cmp(&b_bc) {
+            Some(Ordering::Equal) | None if bias_right => b.i.cmp(&a.i),
+            Some(Ordering::Equal) | None => a.i.cmp(&b.i),
+            Some(ordering) => ordering,
+        }
+    });
+    unsortable.sort_by_key(|entry| Reverse(entry.i));
+
+    let mut vs = vec![];
+    let (mut sum, mut weight) = (0.0, 0.0);
+    let mut vs_index = consume_unsortable(&mut vs, &mut unsortable, 0);
+    for entry in sortable {
+        vs_index += entry.vs.len();
+        vs.extend(entry.vs);
+        let entry_weight = entry.weight.unwrap_or(0.0);
+        sum += entry.barycenter.unwrap() * entry_weight;
+        weight += entry_weight;
+        vs_index = consume_unsortable(&mut vs, &mut unsortable, vs_index);
+    }
+
+    let has_weight = weight != 0.0 && !weight.is_nan();
+    SubgraphResult {
+        vs,
+        barycenter: has_weight.then_some(sum / weight),
+        weight: if has_weight { weight } else { 0.0 },
+    }
+}
+
+fn consume_unsortable(
+    vs: &mut Vec<String>,
+    unsortable: &mut Vec<ConflictEntry>,
+    mut index: usize,
+) -> usize {
+    while unsortable.last().is_some_and(|last| last.i <= index) {
+        vs.extend(unsortable.pop().unwrap().vs);
+        index += 1;
+    }
+    index
+}
+
+// The weighted number of crossings between edges joining adjacent ranks,
+// counted with an accumulator tree as in Barth et al., "Simple and Efficient
+// Bilayer Cross Counting".
+fn cross_count(g: &DagreGraph, layering: &[Vec<String>]) -> usize {
+    layering
+        .windows(2)
+        .map(|layers| two_layer_cross_count(g, &layers[0], &layers[1]))
+        .sum::<f64>() as usize
+}
+
+fn two_layer_cross_count(g: &DagreGraph, north: &[String], south: &[String]) -> f64 {
+    let south_pos: HashMap<&String, usize> =
+        south.iter().enumerate().map(|(i, v)| (v, i)).collect();
+    let mut south_entries = vec![];
+    for v in north {
+        let mut entries: Vec<(usize, f64)> = g
+            .out_edges(v, None)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|e| {
+                let weight = g
+                    .edge_with_obj(e)
+                    .and_then(|edge| edge.weight)
+                    .unwrap_or(0.0);
+                Some((*south_pos.get(&e.w)?, weight as f64))
+            })
+            .collect();
+        entries.sort_by_key(|(pos, _)| *pos);
+        south_entries.extend(entries);
+    }
+
+    let mut first_index = 1;
+    while first_index < south.len() {
+        first_index <<= 1;
+    }
+    let mut tree = vec![0.0; 2 * first_index - 1];
+    first_index -= 1;
+
+    let mut cc = 0.0;
+    for (pos, weight) in south_entries {
+        let mut index = pos + first_index;
+        tree[index] += weight;
+        let mut weight_sum = 0.0;
+        while index > 0 {
+            if index % 2 == 1 {
+                weight_sum += tree[index + 1];
+            }
+            index = (index - 1) >> 1;
+            tree[index] += weight;
+        }
+        cc += weight * weight_sum;
+    }
+    cc
+}
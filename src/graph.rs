@@ -0,0 +1,1796 @@
+use graphlib_rust::graph::{DefaultEdgeLabel, DefaultNodeLabel, GRAPH_NODE};
+use graphlib_rust::{Edge, Graph, GraphOption};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::fmt::{self, Debug};
+
+/// An edge for `GraphExt::set_edges`: `(v, w, label, name)`, as passed to
+/// `set_edge`.
+pub type EdgeSpec<E> = (String, String, Option<E>, Option<String>);
+
+/// Errors from `GraphExt::set_parent_checked`, for callers that need to tell
+/// them apart without matching on the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// `parent` is `child` itself or one of its descendants.
+    WouldCreateCycle { parent: String, child: String },
+    /// The graph wasn't created with `compound: Some(true)`.
+    NotCompound,
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::WouldCreateCycle { parent, child } => write!(
+                f,
+                "Setting {} as parent of {} would create a cycle",
+                parent, child
+            ),
+            GraphError::NotCompound => f.write_str("Cannot set parent in a non-compound graph"),
+        }
+    }
+}
+
+impl Error for GraphError {}
+
+/// Helpers for `graphlib_rust::Graph` that graphlib provides but the Rust port
+/// doesn't. Everything here is built on the graph's public API.
+pub trait GraphExt<GL, N, E> {
+    /// Serializes the graph in graphlib's `json.write` format: the options,
+    /// nodes with their labels and parents, edges, and the graph label.
+    fn to_json(&self) -> Value
+    where
+        GL: Serialize,
+        N: Serialize,
+        E: Serialize;
+
+    /// Rebuilds a graph from the output of `to_json`.
+    fn from_json(value: &Value) -> Result<Self, Box<dyn Error>>
+    where
+        Self: Sized,
+        GL: DeserializeOwned,
+        N: DeserializeOwned,
+        E: DeserializeOwned;
+
+    /// Deep-copies the nodes, edges, compound parents and graph label. The
+    /// default node/edge label functions are not preserved, since boxed
+    /// closures can't be cloned; the copy falls back to `Default` labels.
+    fn clone_graph(&self) -> Self
+    where
+        GL: Clone;
+
+    /// The subgraph induced by `nodes`: those of them present in the graph,
+    /// plus every edge with both ends among them. Like `filter_nodes`, a kept
+    /// node whose parent was dropped moves up to its nearest kept ancestor.
+    /// Unlike it, edge names and the graph label are kept.
+    fn subgraph(&self, nodes: &HashSet<String>) -> Self
+    where
+        GL: Clone;
+
+    /// A copy of the graph that keeps every node, compound parent and the
+    /// graph label, but only the edges for which `f` returns true. The
+    /// edge-level counterpart to `filter_nodes`.
+    fn filter_edges(&self, f: impl Fn(&Edge) -> bool) -> Self
+    where
+        GL: Clone;
+
+    /// A copy of the graph with every edge reversed, keeping edge names and
+    /// labels, nodes, compound parents and the graph label. Successors in the
+    /// copy are predecessors in the original. An undirected graph comes back
+    /// unchanged.
+    fn transpose(&self) -> Self
+    where
+        GL: Clone;
+
+    /// An undirected copy of the graph, keeping nodes, compound parents, the
+    /// graph label and edge names and labels. Edges running both ways
+    /// between two nodes become one edge, labelled like whichever comes
+    /// first in `edges()`. An undirected graph comes back unchanged.
+    fn to_undirected(&self) -> Self
+    where
+        GL: Clone;
+
+    /// A directed copy of the graph with each edge replaced by a pair, one
+    /// each way, sharing its name and label. A self-loop stays a single edge.
+    /// Nodes, compound parents and the graph label carry over. A directed
+    /// graph comes back unchanged.
+    fn to_directed(&self) -> Self
+    where
+        GL: Clone;
+
+    /// Graphviz DOT source for the graph, for eyeballing an input while
+    /// debugging a layout. `node_attrs` returns the attribute list for a node,
+    /// e.g. `label="a"`, or an empty string for none. Compound parents become
+    /// `subgraph cluster_*` blocks around their children, and edge names are
+    /// used as labels in multigraphs.
+    fn to_dot(&self, node_attrs: impl Fn(&String, &N) -> String) -> String;
+
+    /// Counts the edges between `v` and `w`, which can exceed one in a
+    /// multigraph. Undirected graphs ignore argument order.
+    fn edge_count_between(&self, v: &str, w: &str) -> usize;
+
+    /// Removes every node and edge, keeping the graph's options, its label and
+    /// its default node and edge label functions, so one graph can be reused
+    /// instead of building a new one. graphlib's maps are private, so this
+    /// goes through `remove_node` and costs as much as removing each node.
+    fn clear(&mut self);
+
+    /// Gives nodes added without a label a copy of `label`. Shorthand for
+    /// `set_default_node_label(DefaultNodeLabel::Val(Some(label)))`.
+    fn set_default_node_value(&mut self, label: N) -> &mut Self;
+
+    /// Labels nodes added without a label by calling `f` with the node's id.
+    /// `f` must return `Some`: graphlib unwraps the result.
+    fn set_default_node_fn(&mut self, f: impl Fn(String) -> Option<N> + 'static) -> &mut Self;
+
+    /// Gives edges added without a label a copy of `label`.
+    fn set_default_edge_value(&mut self, label: E) -> &mut Self;
+
+    /// Labels edges added without a label by calling `f` with graphlib's
+    /// internal id for the edge. `f` must return `Some`, as for nodes.
+    fn set_default_edge_fn(&mut self, f: impl Fn(String) -> Option<E> + 'static) -> &mut Self;
+
+    /// A copy of `v`'s label, or `N::default()` if there is no such node. The
+    /// graph's default node label function isn't consulted.
+    fn node_or_default(&self, v: &str) -> N;
+
+    /// A copy of the label of the edge from `v` to `w`, or `E::default()` if
+    /// there is no such edge.
+    fn edge_or_default(&self, v: &str, w: &str, name: Option<&str>) -> E;
+
+    /// Applies `f` to `v`'s label in place. False, with `f` not called, if
+    /// there is no such node.
+    fn update_node(&mut self, v: &str, f: impl FnOnce(&mut N)) -> bool;
+
+    /// `v`'s slot in the graph, for inserting or updating its label in one
+    /// call, like `HashMap::entry`.
+    fn node_entry(&mut self, v: &str) -> NodeEntry<'_, GL, N, E>
+    where
+        GL: Default;
+
+    /// The label of the edge from `v` to `w`, adding the edge with the graph's
+    /// default edge label first if it's missing. Errors like `set_edge`, i.e.
+    /// for a named edge in a graph that isn't a multigraph.
+    fn edge_or_insert_default(
+        &mut self,
+        v: &str,
+        w: &str,
+        name: Option<&str>,
+    ) -> Result<&mut E, Box<dyn Error>>;
+
+    /// Removes `v` exactly like `remove_node`, including incident edges and
+    /// compound membership, but hands back its label instead of dropping it.
+    fn take_node(&mut self, v: &str) -> Option<N>;
+
+    /// Gives node `old` the id `new`, keeping its label, its edges with their
+    /// names and labels, its compound parent and its children. graphlib keys
+    /// edges by their endpoints, so incident edges are removed and added
+    /// again, and come last in `edges()` afterwards. Errors, changing
+    /// nothing, if `old` is missing or `new` already exists.
+    fn rename_node(&mut self, old: &str, new: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Replaces the nodes in `members` with the single node `new_id`. Edges
+    /// between two members are dropped, and edges from the rest of the graph
+    /// are moved onto `new_id`, keeping their labels and names. Where two of
+    /// them end up joining the same nodes under the same name, the first in
+    /// `edges()` order is kept. `new_id` keeps its own label if it's one of
+    /// the members. In a compound graph it goes under the members' parent
+    /// if they share one, and takes over their other children. Errors,
+    /// changing nothing, if a member is missing, `members` is empty or
+    /// `new_id` is already a node outside `members`.
+    fn collapse_nodes(
+        &mut self,
+        members: &HashSet<String>,
+        new_id: &str,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// The chain of compound parents of `v`, nearest first, not including the
+    /// synthetic root.
+    fn ancestors(&self, v: &str) -> Vec<String>;
+
+    /// Every node nested under `v`, in pre-order over child insertion order.
+    fn descendants(&self, v: &str) -> Vec<String>;
+
+    /// Number of nodes nested under `v` at any depth, not counting `v`. The
+    /// length of `descendants(v)` without collecting it; 0 for a leaf.
+    fn subtree_size(&self, v: &str) -> usize;
+
+    /// Top-level nodes of the compound hierarchy, i.e. those without a parent.
+    /// Every node is top-level in a non-compound graph.
+    fn root_children(&self) -> Vec<String>;
+
+    /// Nodes with no compound children.
+    fn leaf_nodes(&self) -> Vec<String>;
+
+    /// Nodes with no edges at all, self-loops included, and no compound
+    /// children. Unlike `sources` and `sinks`, which each allow edges the
+    /// other way.
+    fn isolated_nodes(&self) -> Vec<String>;
+
+    /// Number of edges pointing at `v`, or 0 if `v` isn't in the graph.
+    fn in_degree(&self, v: &str) -> usize;
+
+    /// Number of edges leaving `v`, or 0 if `v` isn't in the graph.
+    fn out_degree(&self, v: &str) -> usize;
+
+    /// Number of edges incident on `v`. A self-loop counts once.
+    fn degree(&self, v: &str) -> usize;
+
+    /// Predecessors then successors of `v`, each listed once. Like graphlib's
+    /// `neighbors`, this is `Some` (possibly empty) for any node in the graph
+    /// and `None` only for a missing one, but the order is stable rather than
+    /// hash order.
+    fn adjacent(&self, v: &str) -> Option<Vec<String>>;
+
+    /// Checks the graph's internal bookkeeping for drift: the node and edge
+    /// counts match the node and edge lists, every edge's endpoints exist and
+    /// list the edge among their out-/in-edges, and compound parents and
+    /// children agree with each other. Returns a description of the first
+    /// inconsistency found. Meant for tests and debug assertions; it walks the
+    /// whole graph.
+    fn validate(&self) -> Result<(), String>;
+
+    /// Whether a path of at least one edge leads from `from` to `to`, following
+    /// successors (or neighbors if undirected). `from == to` holds only
+    /// through a self-loop, not a longer cycle. False if either node is
+    /// missing.
+    fn has_path(&self, from: &str, to: &str) -> bool;
+
+    /// `set_parent` with a typed error. Also catches making a node its own
+    /// parent, which graphlib_rust's cycle check misses.
+    fn set_parent_checked(
+        &mut self,
+        v: &str,
+        parent: Option<&str>,
+    ) -> Result<&mut Self, GraphError>;
+
+    /// Calls `set_edge` for each entry in order, stopping at the first error.
+    /// Edges set before the failing one are kept.
+    fn set_edges(&mut self, edges: &[EdgeSpec<E>]) -> Result<&mut Self, Box<dyn Error>>;
+
+    /// Adds `other`'s nodes, edges and compound parents to this graph. Labels
+    /// and parents from `other` win where both graphs have the same node or
+    /// edge. Errors up front if the graphs disagree on being directed or a
+    /// multigraph; a failing `set_edge` or `set_parent` stops the merge
+    /// partway, keeping what was already added.
+    fn merge(&mut self, other: &Self) -> Result<&mut Self, Box<dyn Error>>;
+
+    /// A copy of the graph with every node label replaced by `f(v, label)`.
+    /// Edges, edge labels, compound parents and the graph label carry over.
+    fn map_nodes<N2: Default + Clone + Debug>(
+        &self,
+        f: impl Fn(&String, &N) -> N2,
+    ) -> Graph<GL, N2, E>
+    where
+        GL: Clone + Default;
+
+    /// A copy of the graph with every edge label replaced by `f(edge, label)`.
+    /// Nodes, node labels, compound parents and the graph label carry over.
+    fn map_edges<E2: Default + Clone + Debug>(
+        &self,
+        f: impl Fn(&Edge, &E) -> E2,
+    ) -> Graph<GL, N, E2>
+    where
+        GL: Clone + Default;
+}
+
+impl<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug> GraphExt<GL, N, E>
+    for Graph<GL, N, E>
+{
+    fn to_json(&self) -> Value
+    where
+        GL: Serialize,
+        N: Serialize,
+        E: Serialize,
+    {
+        let nodes: Vec<Value> = self
+            .nodes()
+            .into_iter()
+            .map(|v| {
+                let mut node = Map::new();
+                if let Some(label) = self.node(&v) {
+                    node.insert("value".to_string(), json!(label));
+                }
+                if let Some(parent) = self.parent(&v) {
+                    node.insert("parent".to_string(), json!(parent));
+                }
+                node.insert("v".to_string(), json!(v));
+                Value::Object(node)
+            })
+            .collect();
+
+        let edges: Vec<Value> = self
+            .edges()
+            .into_iter()
+            .map(|e| {
+                let mut edge = Map::new();
+                if let Some(label) = self.edge_with_obj(&e) {
+                    edge.insert("value".to_string(), json!(label));
+                }
+                if let Some(name) = &e.name {
+                    edge.insert("name".to_string(), json!(name));
+                }
+                edge.insert("v".to_string(), json!(e.v));
+                edge.insert("w".to_string(), json!(e.w));
+                Value::Object(edge)
+            })
+            .collect();
+
+        json!({
+            "options": {
+                "directed": self.is_directed(),
+                "multigraph": self.is_multigraph(),
+                "compound": self.is_compound(),
+            },
+            "nodes": nodes,
+            "edges": edges,
+            "value": self.graph(),
+        })
+    }
+
+    fn from_json(value: &Value) -> Result<Self, Box<dyn Error>>
+    where
+        GL: DeserializeOwned,
+        N: DeserializeOwned,
+        E: DeserializeOwned,
+    {
+        let option = |name: &str| value["options"][name].as_bool();
+        let mut g: Graph<GL, N, E> = Graph::new(Some(GraphOption {
+            directed: option("directed"),
+            multigraph: option("multigraph"),
+            compound: option("compound"),
+        }));
+
+        if !value["value"].is_null() {
+            g.set_graph(GL::deserialize(&value["value"])?);
+        }
+
+        let string_field = |entry: &Value, field: &str| -> Result<String, Box<dyn Error>> {
+            match entry[field].as_str() {
+                Some(s) => Ok(s.to_string()),
+                None => Err(format!("Missing \"{}\" in {}", field, entry).into()),
+            }
+        };
+        let label = |entry: &Value| -> Result<Option<N>, serde_json::Error> {
+            match &entry["value"] {
+                Value::Null => Ok(None),
+                label => N::deserialize(label).map(Some),
+            }
+        };
+
+        let nodes = value["nodes"].as_array().cloned().unwrap_or_default();
+        for entry in &nodes {
+            g.set_node(string_field(entry, "v")?, label(entry)?);
+        }
+        // Parents are restored once every node exists, since a parent may be
+        // listed after its children.
+        for entry in &nodes {
+            if let Some(parent) = entry["parent"].as_str() {
+                g.set_parent(&string_field(entry, "v")?, Some(parent.to_string()))?;
+            }
+        }
+
+        for entry in value["edges"].as_array().cloned().unwrap_or_default() {
+            let edge_label = match &entry["value"] {
+                Value::Null => None,
+                label => Some(E::deserialize(label)?),
+            };
+            let name = entry["name"].as_str().map(|name| name.to_string());
+            g.set_edge(
+                &string_field(&entry, "v")?,
+                &string_field(&entry, "w")?,
+                edge_label,
+                name,
+            )?;
+        }
+
+        Ok(g)
+    }
+
+    fn clone_graph(&self) -> Self
+    where
+        GL: Clone,
+    {
+        let mut copy = empty_copy(self);
+        for v in self.nodes() {
+            copy.set_node(v.clone(), self.node(&v).cloned());
+        }
+        copy_parents(self, &mut copy);
+        for e in self.edges() {
+            let _ = copy.set_edge(&e.v, &e.w, self.edge_with_obj(&e).cloned(), e.name.clone());
+        }
+        copy
+    }
+
+    fn subgraph(&self, nodes: &HashSet<String>) -> Self
+    where
+        GL: Clone,
+    {
+        let mut copy = empty_copy(self);
+        for v in self.nodes().into_iter().filter(|v| nodes.contains(v)) {
+            copy.set_node(v.clone(), self.node(&v).cloned());
+        }
+        if self.is_compound() {
+            for v in copy.nodes() {
+                let parent = self
+                    .ancestors(&v)
+                    .into_iter()
+                    .find(|ancestor| nodes.contains(ancestor));
+                if parent.is_some() {
+                    let _ = copy.set_parent(&v, parent);
+                }
+            }
+        }
+        for e in self.edges() {
+            if nodes.contains(&e.v) && nodes.contains(&e.w) {
+                let _ = copy.set_edge(&e.v, &e.w, self.edge_with_obj(&e).cloned(), e.name.clone());
+            }
+        }
+        copy
+    }
+
+    fn filter_edges(&self, f: impl Fn(&Edge) -> bool) -> Self
+    where
+        GL: Clone,
+    {
+        let mut copy = empty_copy(self);
+        for v in self.nodes() {
+            copy.set_node(v.clone(), self.node(&v).cloned());
+        }
+        copy_parents(self, &mut copy);
+        for e in self.edges().into_iter().filter(|e| f(e)) {
+            let _ = copy.set_edge(&e.v, &e.w, self.edge_with_obj(&e).cloned(), e.name.clone());
+        }
+        copy
+    }
+
+    fn transpose(&self) -> Self
+    where
+        GL: Clone,
+    {
+        if !self.is_directed() {
+            return self.clone_graph();
+        }
+        let mut copy = empty_copy(self);
+        for v in self.nodes() {
+            copy.set_node(v.clone(), self.node(&v).cloned());
+        }
+        copy_parents(self, &mut copy);
+        for e in self.edges() {
+            let _ = copy.set_edge(&e.w, &e.v, self.edge_with_obj(&e).cloned(), e.name.clone());
+        }
+        copy
+    }
+
+    fn to_undirected(&self) -> Self
+    where
+        GL: Clone,
+    {
+        if !self.is_directed() {
+            return self.clone_graph();
+        }
+        let mut copy = empty_copy_directed(self, false);
+        for v in self.nodes() {
+            copy.set_node(v.clone(), self.node(&v).cloned());
+        }
+        copy_parents(self, &mut copy);
+        for e in self.edges() {
+            if !copy.has_edge(&e.v, &e.w, e.name.clone()) {
+                let _ = copy.set_edge(&e.v, &e.w, self.edge_with_obj(&e).cloned(), e.name.clone());
+            }
+        }
+        copy
+    }
+
+    fn to_directed(&self) -> Self
+    where
+        GL: Clone,
+    {
+        if self.is_directed() {
+            return self.clone_graph();
+        }
+        let mut copy = empty_copy_directed(self, true);
+        for v in self.nodes() {
+            copy.set_node(v.clone(), self.node(&v).cloned());
+        }
+        copy_parents(self, &mut copy);
+        for e in self.edges() {
+            let label = self.edge_with_obj(&e).cloned();
+            let _ = copy.set_edge(&e.v, &e.w, label.clone(), e.name.clone());
+            let _ = copy.set_edge(&e.w, &e.v, label, e.name.clone());
+        }
+        copy
+    }
+
+    fn to_dot(&self, node_attrs: impl Fn(&String, &N) -> String) -> String {
+        fn write_node<GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>(
+            g: &Graph<GL, N, E>,
+            v: &String,
+            node_attrs: &impl Fn(&String, &N) -> String,
+            indent: usize,
+            out: &mut String,
+        ) {
+            let pad = "  ".repeat(indent);
+            let children = if g.is_compound() {
+                g.children(v)
+            } else {
+                vec![]
+            };
+            if children.is_empty() {
+                let attrs = g
+                    .node(v)
+                    .map(|label| node_attrs(v, label))
+                    .unwrap_or_default();
+                if attrs.is_empty() {
+                    out.push_str(&format!("{}{};\n", pad, dot_id(v)));
+                } else {
+                    out.push_str(&format!("{}{} [{}];\n", pad, dot_id(v), attrs));
+                }
+                return;
+            }
+            out.push_str(&format!(
+                "{}subgraph {} {{\n",
+                pad,
+                dot_id(&format!("cluster_{}", v))
+            ));
+            out.push_str(&format!("{}  label={};\n", pad, dot_id(v)));
+            for child in &children {
+                write_node(g, child, node_attrs, indent + 1, out);
+            }
+            out.push_str(&format!("{}}}\n", pad));
+        }
+
+        let (keyword, arrow) = if self.is_directed() {
+            ("digraph", "->")
+        } else {
+            ("graph", "--")
+        };
+        let mut out = format!("{} {{\n", keyword);
+        for v in self.root_children() {
+            write_node(self, &v, &node_attrs, 1, &mut out);
+        }
+        for e in self.edges() {
+            out.push_str(&format!("  {} {} {}", dot_id(&e.v), arrow, dot_id(&e.w)));
+            match &e.name {
+                Some(name) if self.is_multigraph() => {
+                    out.push_str(&format!(" [label={}];\n", dot_id(name)))
+                }
+                _ => out.push_str(";\n"),
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn edge_count_between(&self, v: &str, w: &str) -> usize {
+        if self.is_directed() {
+            return self
+                .out_edges(&v.to_string(), Some(w.to_string()))
+                .map_or(0, |edges| edges.len());
+        }
+
+        // A self-loop shows up in both the in- and out-edges of `v`, so count
+        // distinct edge names rather than entries.
+        let names: HashSet<Option<String>> = self
+            .node_edges(&v.to_string(), None)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|e| (e.v == v && e.w == w) || (e.v == w && e.w == v))
+            .map(|e| e.name)
+            .collect();
+        names.len()
+    }
+
+    fn clear(&mut self) {
+        // `remove_node` drops edge names when removing incident edges, which
+        // leaves named edges behind, so those go first.
+        for e in self.edges() {
+            self.remove_edge(&e.v, &e.w, e.name.clone());
+        }
+        for v in self.nodes() {
+            self.remove_node(&v);
+        }
+    }
+
+    fn set_default_node_value(&mut self, label: N) -> &mut Self {
+        self.set_default_node_label(DefaultNodeLabel::Val(Some(label)))
+    }
+
+    fn set_default_node_fn(&mut self, f: impl Fn(String) -> Option<N> + 'static) -> &mut Self {
+        self.set_default_node_label(DefaultNodeLabel::Func(Box::new(f)))
+    }
+
+    fn set_default_edge_value(&mut self, label: E) -> &mut Self {
+        self.set_default_edge_label(DefaultEdgeLabel::Val(Some(label)))
+    }
+
+    fn set_default_edge_fn(&mut self, f: impl Fn(String) -> Option<E> + 'static) -> &mut Self {
+        self.set_default_edge_label(DefaultEdgeLabel::Func(Box::new(f)))
+    }
+
+    fn node_or_default(&self, v: &str) -> N {
+        self.node(&v.to_string()).cloned().unwrap_or_default()
+    }
+
+    fn edge_or_default(&self, v: &str, w: &str, name: Option<&str>) -> E {
+        self.edge(&v.to_string(), &w.to_string(), name.map(str::to_string))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn update_node(&mut self, v: &str, f: impl FnOnce(&mut N)) -> bool {
+        match self.node_mut(&v.to_string()) {
+            Some(label) => {
+                f(label);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn node_entry(&mut self, v: &str) -> NodeEntry<'_, GL, N, E> {
+        NodeEntry {
+            graph: self,
+            v: v.to_string(),
+        }
+    }
+
+    fn edge_or_insert_default(
+        &mut self,
+        v: &str,
+        w: &str,
+        name: Option<&str>,
+    ) -> Result<&mut E, Box<dyn Error>> {
+        let (v, w, name) = (v.to_string(), w.to_string(), name.map(str::to_string));
+        // Without a label, `set_edge` leaves an existing edge's label alone.
+        self.set_edge(&v, &w, None, name.clone())?;
+        Ok(self.edge_mut(&v, &w, name).unwrap())
+    }
+
+    fn take_node(&mut self, v: &str) -> Option<N> {
+        let v = v.to_string();
+        // Moves the label out without cloning; the node is removed right after.
+        let label = self.node_mut(&v).map(std::mem::take);
+        self.remove_node(&v);
+        label
+    }
+
+    fn rename_node(&mut self, old: &str, new: &str) -> Result<(), Box<dyn Error>> {
+        let (old, new) = (old.to_string(), new.to_string());
+        if !self.has_node(&old) {
+            return Err(format!("Cannot rename missing node {}", old).into());
+        }
+        if self.has_node(&new) {
+            return Err(format!("Cannot rename {} to existing node {}", old, new).into());
+        }
+
+        let renamed = |v: &String| if *v == old { new.clone() } else { v.clone() };
+        let edges: Vec<(Edge, Option<E>)> = self
+            .node_edges(&old, None)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|e| {
+                let label = self.edge_with_obj(&e).cloned();
+                (e, label)
+            })
+            .collect();
+        let parent = self.parent(&old).cloned();
+        let children = self.children(&old);
+
+        // `remove_node` drops edge names when removing incident edges, which
+        // leaves named edges behind, so those go first.
+        for (e, _) in &edges {
+            self.remove_edge(&e.v, &e.w, e.name.clone());
+        }
+        let label = self.take_node(&old);
+        self.set_node(new.clone(), label);
+        if let Some(parent) = parent {
+            self.set_parent(&new, Some(parent))?;
+        }
+        for child in children {
+            self.set_parent(&child, Some(new.clone()))?;
+        }
+        for (e, label) in edges {
+            self.set_edge(&renamed(&e.v), &renamed(&e.w), label, e.name)?;
+        }
+        Ok(())
+    }
+
+    fn collapse_nodes(
+        &mut self,
+        members: &HashSet<String>,
+        new_id: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if members.is_empty() {
+            return Err("No nodes to collapse".into());
+        }
+        if let Some(v) = members.iter().find(|v| !self.has_node(v)) {
+            return Err(format!("Cannot collapse missing node {}", v).into());
+        }
+        let new_id = new_id.to_string();
+        if self.has_node(&new_id) && !members.contains(&new_id) {
+            return Err(format!("{} is already in the graph", new_id).into());
+        }
+
+        let outside = |v: &String| -> String {
+            if members.contains(v) {
+                new_id.clone()
+            } else {
+                v.clone()
+            }
+        };
+        let edges: Vec<(Edge, Option<E>)> = self
+            .edges()
+            .into_iter()
+            .filter(|e| members.contains(&e.v) != members.contains(&e.w))
+            .map(|e| {
+                let label = self.edge_with_obj(&e).cloned();
+                let moved = Edge {
+                    v: outside(&e.v),
+                    w: outside(&e.w),
+                    name: e.name,
+                };
+                (moved, label)
+            })
+            .collect();
+        let mut parents: HashSet<Option<String>> = HashSet::new();
+        let mut children: Vec<String> = vec![];
+        for v in members {
+            if !self
+                .parent(v)
+                .is_some_and(|parent| members.contains(parent))
+            {
+                parents.insert(self.parent(v).cloned());
+            }
+            children.extend(
+                self.children(v)
+                    .into_iter()
+                    .filter(|child| !members.contains(child)),
+            );
+        }
+        let label = self.node(&new_id).cloned();
+
+        for v in members {
+            self.remove_node(v);
+        }
+        self.set_node(new_id.clone(), label);
+        if let (1, Some(Some(parent))) = (parents.len(), parents.iter().next()) {
+            self.set_parent(&new_id, Some(parent.clone()))?;
+        }
+        for child in children {
+            self.set_parent(&child, Some(new_id.clone()))?;
+        }
+        for (e, label) in edges {
+            if !self.has_edge(&e.v, &e.w, e.name.clone()) {
+                self.set_edge(&e.v, &e.w, label, e.name)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn ancestors(&self, v: &str) -> Vec<String> {
+        let mut ancestors: Vec<String> = vec![];
+        let mut current = v.to_string();
+        while let Some(parent) = self.parent(&current) {
+            ancestors.push(parent.clone());
+            current = parent.clone();
+        }
+        ancestors
+    }
+
+    fn descendants(&self, v: &str) -> Vec<String> {
+        let mut descendants: Vec<String> = vec![];
+        let mut stack: Vec<String> = self.children(&v.to_string());
+        stack.reverse();
+        while let Some(child) = stack.pop() {
+            stack.extend(self.children(&child).into_iter().rev());
+            descendants.push(child);
+        }
+        descendants
+    }
+
+    fn subtree_size(&self, v: &str) -> usize {
+        let mut size = 0;
+        let mut stack: Vec<String> = self.children(&v.to_string());
+        while let Some(child) = stack.pop() {
+            stack.extend(self.children(&child));
+            size += 1;
+        }
+        size
+    }
+
+    fn root_children(&self) -> Vec<String> {
+        self.children(&GRAPH_NODE.to_string())
+            .into_iter()
+            .filter(|v| self.has_node(v))
+            .collect()
+    }
+
+    fn leaf_nodes(&self) -> Vec<String> {
+        self.nodes()
+            .into_iter()
+            .filter(|v| self.children(v).is_empty())
+            .collect()
+    }
+
+    fn isolated_nodes(&self) -> Vec<String> {
+        self.nodes()
+            .into_iter()
+            .filter(|v| self.degree(v) == 0 && self.children(v).is_empty())
+            .collect()
+    }
+
+    // graphlib keeps its adjacency maps private, so these go through the edge
+    // lists and cost O(degree) rather than O(1).
+    fn in_degree(&self, v: &str) -> usize {
+        self.in_edges(&v.to_string(), None)
+            .map_or(0, |edges| edges.len())
+    }
+
+    fn out_degree(&self, v: &str) -> usize {
+        self.out_edges(&v.to_string(), None)
+            .map_or(0, |edges| edges.len())
+    }
+
+    fn degree(&self, v: &str) -> usize {
+        let self_loops = self
+            .out_edges(&v.to_string(), Some(v.to_string()))
+            .map_or(0, |edges| edges.len());
+        self.in_degree(v) + self.out_degree(v) - self_loops
+    }
+
+    fn adjacent(&self, v: &str) -> Option<Vec<String>> {
+        let v = v.to_string();
+        if !self.has_node(&v) {
+            return None;
+        }
+        let mut seen: HashSet<String> = HashSet::new();
+        let adjacent = self
+            .predecessors(&v)
+            .unwrap_or_default()
+            .into_iter()
+            .chain(self.successors(&v).unwrap_or_default())
+            .filter(|w| seen.insert(w.clone()))
+            .collect();
+        Some(adjacent)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let nodes = self.nodes();
+        if self.node_count() != nodes.len() {
+            return Err(format!(
+                "node_count is {} but there are {} nodes",
+                self.node_count(),
+                nodes.len()
+            ));
+        }
+        let edges = self.edges();
+        if self.edge_count() != edges.len() {
+            return Err(format!(
+                "edge_count is {} but there are {} edges",
+                self.edge_count(),
+                edges.len()
+            ));
+        }
+
+        for e in &edges {
+            for end in [&e.v, &e.w] {
+                if !self.has_node(end) {
+                    return Err(format!("Edge {:?} references missing node {}", e, end));
+                }
+            }
+            let listed = |edges: Option<Vec<Edge>>| {
+                edges
+                    .unwrap_or_default()
+                    .iter()
+                    .any(|f| f.v == e.v && f.w == e.w && f.name == e.name)
+            };
+            if !listed(self.out_edges(&e.v, None)) {
+                return Err(format!(
+                    "Edge {:?} is missing from the out-edges of {}",
+                    e, e.v
+                ));
+            }
+            if !listed(self.in_edges(&e.w, None)) {
+                return Err(format!(
+                    "Edge {:?} is missing from the in-edges of {}",
+                    e, e.w
+                ));
+            }
+        }
+
+        if !self.is_compound() {
+            return Ok(());
+        }
+        for v in &nodes {
+            let parent = self.parent(v).cloned().unwrap_or(GRAPH_NODE.to_string());
+            if parent != GRAPH_NODE && !self.has_node(&parent) {
+                return Err(format!("{} has missing parent {}", v, parent));
+            }
+            if !self.children(&parent).contains(v) {
+                return Err(format!(
+                    "{} is not among the children of its parent {:?}",
+                    v, parent
+                ));
+            }
+            for child in self.children(v) {
+                if self.parent(&child) != Some(v) {
+                    return Err(format!(
+                        "{} lists {} as a child, but not vice versa",
+                        v, child
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn has_path(&self, from: &str, to: &str) -> bool {
+        if !self.has_node(&from.to_string()) || !self.has_node(&to.to_string()) {
+            return false;
+        }
+        if from == to {
+            return self
+                .out_edges(&from.to_string(), Some(to.to_string()))
+                .is_some_and(|edges| !edges.is_empty());
+        }
+        let next = |v: &str| -> Vec<String> {
+            if self.is_directed() {
+                self.successors(&v.to_string()).unwrap_or_default()
+            } else {
+                self.adjacent(v).unwrap_or_default()
+            }
+        };
+
+        let mut visited: HashSet<String> = HashSet::new();
+        let mut queue: VecDeque<String> = next(from).into();
+        while let Some(v) = queue.pop_front() {
+            if v == to {
+                return true;
+            }
+            if visited.insert(v.clone()) {
+                queue.extend(next(&v));
+            }
+        }
+        false
+    }
+
+    fn set_parent_checked(
+        &mut self,
+        v: &str,
+        parent: Option<&str>,
+    ) -> Result<&mut Self, GraphError> {
+        if !self.is_compound() {
+            return Err(GraphError::NotCompound);
+        }
+        if let Some(parent) = parent {
+            if parent == v || self.ancestors(parent).iter().any(|ancestor| ancestor == v) {
+                return Err(GraphError::WouldCreateCycle {
+                    parent: parent.to_string(),
+                    child: v.to_string(),
+                });
+            }
+        }
+        // Both of `set_parent`'s errors were ruled out above.
+        self.set_parent(&v.to_string(), parent.map(str::to_string))
+            .map_err(|_| GraphError::NotCompound)?;
+        Ok(self)
+    }
+
+    fn set_edges(&mut self, edges: &[EdgeSpec<E>]) -> Result<&mut Self, Box<dyn Error>> {
+        for (v, w, label, name) in edges {
+            self.set_edge(v, w, label.clone(), name.clone())?;
+        }
+        Ok(self)
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<&mut Self, Box<dyn Error>> {
+        if self.is_directed() != other.is_directed() {
+            return Err("Cannot merge a directed graph with an undirected one".into());
+        }
+        if self.is_multigraph() != other.is_multigraph() {
+            return Err("Cannot merge a multigraph with a simple graph".into());
+        }
+
+        for v in other.nodes() {
+            self.set_node(v.clone(), other.node(&v).cloned());
+        }
+        for e in other.edges() {
+            self.set_edge(&e.v, &e.w, other.edge_with_obj(&e).cloned(), e.name.clone())?;
+        }
+        if other.is_compound() {
+            for v in other.nodes() {
+                if let Some(parent) = other.parent(&v) {
+                    self.set_parent_checked(&v, Some(parent))?;
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    fn map_nodes<N2: Default + Clone + Debug>(
+        &self,
+        f: impl Fn(&String, &N) -> N2,
+    ) -> Graph<GL, N2, E>
+    where
+        GL: Clone,
+    {
+        let mut copy = empty_copy(self);
+        for v in self.nodes() {
+            let label = self.node(&v).map(|label| f(&v, label));
+            copy.set_node(v, label);
+        }
+        copy_parents(self, &mut copy);
+        for e in self.edges() {
+            let _ = copy.set_edge(&e.v, &e.w, self.edge_with_obj(&e).cloned(), e.name.clone());
+        }
+        copy
+    }
+
+    fn map_edges<E2: Default + Clone + Debug>(
+        &self,
+        f: impl Fn(&Edge, &E) -> E2,
+    ) -> Graph<GL, N, E2>
+    where
+        GL: Clone,
+    {
+        let mut copy = empty_copy(self);
+        for v in self.nodes() {
+            copy.set_node(v.clone(), self.node(&v).cloned());
+        }
+        copy_parents(self, &mut copy);
+        for e in self.edges() {
+            let label = self.edge_with_obj(&e).map(|label| f(&e, label));
+            let _ = copy.set_edge(&e.v, &e.w, label, e.name.clone());
+        }
+        copy
+    }
+}
+
+/// A node that may or may not be in the graph yet, from
+/// `GraphExt::node_entry`.
+pub struct NodeEntry<'a, GL: Default, N, E> {
+    graph: &'a mut Graph<GL, N, E>,
+    v: String,
+}
+
+impl<'a, GL: Default, N: Default + Clone + Debug, E: Default + Clone + Debug>
+    NodeEntry<'a, GL, N, E>
+{
+    pub fn key(&self) -> &str {
+        &self.v
+    }
+
+    /// Applies `f` to the label if the node exists.
+    pub fn and_modify(self, f: impl FnOnce(&mut N)) -> Self {
+        self.graph.update_node(&self.v, f);
+        self
+    }
+
+    /// The node's label, adding the node with `label` first if it's missing.
+    pub fn or_insert(self, label: N) -> &'a mut N {
+        self.or_insert_with(|| label)
+    }
+
+    /// Like `or_insert`, but only builds the label if it's needed.
+    pub fn or_insert_with(self, f: impl FnOnce() -> N) -> &'a mut N {
+        if !self.graph.has_node(&self.v) {
+            self.graph.set_node(self.v.clone(), Some(f()));
+        }
+        self.graph.node_mut(&self.v).unwrap()
+    }
+
+    /// Like `or_insert`, but a missing node gets the graph's default node
+    /// label, as `set_node` with no label would give it.
+    pub fn or_default(self) -> &'a mut N {
+        self.graph.set_node(self.v.clone(), None);
+        self.graph.node_mut(&self.v).unwrap()
+    }
+}
+
+// A quoted DOT identifier.
+fn dot_id(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A graph with the same options and label as `g`, but no nodes or edges. The
+/// copy's label types may differ from `g`'s.
+fn empty_copy<GL, N, E, N2, E2>(g: &Graph<GL, N, E>) -> Graph<GL, N2, E2>
+where
+    GL: Clone + Default,
+    N: Default + Clone + Debug,
+    E: Default + Clone + Debug,
+    N2: Default + Clone + Debug,
+    E2: Default + Clone + Debug,
+{
+    empty_copy_directed(g, g.is_directed())
+}
+
+/// `empty_copy`, but directed or not as given rather than as `g` is.
+fn empty_copy_directed<GL, N, E, N2, E2>(g: &Graph<GL, N, E>, directed: bool) -> Graph<GL, N2, E2>
+where
+    GL: Clone + Default,
+    N: Default + Clone + Debug,
+    E: Default + Clone + Debug,
+    N2: Default + Clone + Debug,
+    E2: Default + Clone + Debug,
+{
+    let mut copy: Graph<GL, N2, E2> = Graph::new(Some(GraphOption {
+        directed: Some(directed),
+        multigraph: Some(g.is_multigraph()),
+        compound: Some(g.is_compound()),
+    }));
+    copy.set_graph(g.graph().clone());
+    copy
+}
+
+fn copy_parents<GL, N, E, N2, E2>(g: &Graph<GL, N, E>, copy: &mut Graph<GL, N2, E2>)
+where
+    GL: Default,
+    N: Default + Clone + Debug,
+    E: Default + Clone + Debug,
+    N2: Default + Clone + Debug,
+    E2: Default + Clone + Debug,
+{
+    if !g.is_compound() {
+        return;
+    }
+    for v in g.nodes() {
+        if let Some(parent) = g.parent(&v) {
+            let _ = copy.set_parent(&v, Some(parent.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A compound digraph with the given edges, for tests across the crate.
+    pub(crate) fn digraph(edges: &[(&str, &str)]) -> Graph<(), (), ()> {
+        let mut g = Graph::new(Some(GraphOption {
+            directed: Some(true),
+            multigraph: Some(false),
+            compound: Some(true),
+        }));
+        for (v, w) in edges {
+            g.set_edge(&v.to_string(), &w.to_string(), None, None)
+                .unwrap();
+        }
+        g
+    }
+
+    /// A compound digraph with string labels on its nodes and edges.
+    fn labelled(edges: &[(&str, &str, &str)]) -> Graph<(), String, String> {
+        let mut g = Graph::new(Some(GraphOption {
+            directed: Some(true),
+            multigraph: Some(false),
+            compound: Some(true),
+        }));
+        for (v, w, label) in edges {
+            g.set_edge(
+                &v.to_string(),
+                &w.to_string(),
+                Some(label.to_string()),
+                None,
+            )
+            .unwrap();
+        }
+        g
+    }
+
+    #[test]
+    fn rename_node_keeps_edges_and_parent() {
+        let mut g = labelled(&[("a", "b", "ab"), ("b", "c", "bc")]);
+        g.set_node("b".to_string(), Some("middle".to_string()));
+        g.set_parent(&"b".to_string(), Some("group".to_string()))
+            .unwrap();
+        g.set_parent(&"d".to_string(), Some("b".to_string()))
+            .unwrap();
+
+        g.rename_node("b", "x").unwrap();
+        let x = "x".to_string();
+        assert!(!g.has_node(&"b".to_string()));
+        assert_eq!(g.node(&x), Some(&"middle".to_string()));
+        assert_eq!(g.successors(&"a".to_string()), Some(vec![x.clone()]));
+        assert_eq!(g.predecessors(&"c".to_string()), Some(vec![x.clone()]));
+        assert_eq!(g.edge(&"a".to_string(), &x, None), Some(&"ab".to_string()));
+        assert_eq!(g.edge(&x, &"c".to_string(), None), Some(&"bc".to_string()));
+        assert!(g.has_path("a", "c"));
+        assert_eq!(g.parent(&x), Some(&"group".to_string()));
+        assert_eq!(g.children(&x), vec!["d".to_string()]);
+        assert_eq!(g.edge_count(), 2);
+        assert!(g.validate().is_ok());
+
+        assert!(g.rename_node("x", "a").is_err());
+        assert!(g.rename_node("missing", "y").is_err());
+        assert!(g.has_node(&x) && g.edge_count() == 2);
+    }
+
+    #[test]
+    fn collapse_nodes_reroutes_external_edges() {
+        let mut g = labelled(&[("a", "b", "ab"), ("b", "c", "bc"), ("a", "c", "ac")]);
+        let members: HashSet<String> = ["b", "c"].map(String::from).into();
+
+        g.collapse_nodes(&members, "bc").unwrap();
+        let bc = "bc".to_string();
+        assert!(!g.has_node(&"b".to_string()) && !g.has_node(&"c".to_string()));
+        assert_eq!(g.nodes(), vec!["a".to_string(), bc.clone()]);
+        // a's two edges into the triangle land on one edge; the first keeps
+        // its label, and the edge between the members is gone.
+        assert_eq!(g.successors(&"a".to_string()), Some(vec![bc.clone()]));
+        assert_eq!(g.edge(&"a".to_string(), &bc, None), Some(&"ab".to_string()));
+        assert_eq!(g.edge_count(), 1);
+        assert!(g.validate().is_ok());
+
+        let mut g = labelled(&[("a", "b", "ab")]);
+        let missing: HashSet<String> = ["b", "z"].map(String::from).into();
+        assert!(g.collapse_nodes(&missing, "n").is_err());
+        let b: HashSet<String> = ["b".to_string()].into();
+        assert!(g.collapse_nodes(&b, "a").is_err());
+        assert!(g.collapse_nodes(&HashSet::new(), "n").is_err());
+        assert_eq!(g.node_count(), 2);
+    }
+
+    #[test]
+    fn json_round_trip_keeps_parents_names_and_labels() {
+        let mut g: Graph<String, String, String> = Graph::new(Some(GraphOption {
+            directed: Some(true),
+            multigraph: Some(true),
+            compound: Some(true),
+        }));
+        g.set_graph("title".to_string());
+        g.set_node("a".to_string(), Some("A".to_string()));
+        g.set_node("b".to_string(), None);
+        g.set_parent(&"a".to_string(), Some("group".to_string()))
+            .unwrap();
+        g.set_edge(
+            &"a".to_string(),
+            &"b".to_string(),
+            Some("x".to_string()),
+            None,
+        )
+        .unwrap();
+        g.set_edge(
+            &"a".to_string(),
+            &"b".to_string(),
+            Some("y".to_string()),
+            Some("second".to_string()),
+        )
+        .unwrap();
+
+        let json = g.to_json();
+        let copy: Graph<String, String, String> = Graph::from_json(&json).unwrap();
+        assert_eq!(copy.to_json(), json);
+        assert!(copy.is_multigraph() && copy.is_compound());
+        assert_eq!(copy.graph(), &"title".to_string());
+        assert_eq!(copy.parent(&"a".to_string()), Some(&"group".to_string()));
+        assert_eq!(
+            copy.edge(
+                &"a".to_string(),
+                &"b".to_string(),
+                Some("second".to_string())
+            ),
+            Some(&"y".to_string())
+        );
+        assert_eq!(copy.edge_count(), 2);
+
+        let broken = json!({ "nodes": [{ "value": "no id" }] });
+        assert!(Graph::<String, String, String>::from_json(&broken).is_err());
+    }
+
+    #[test]
+    fn clone_graph_is_a_deep_copy() {
+        let mut g = labelled(&[("a", "b", "ab"), ("b", "c", "bc")]);
+        g.set_parent(&"a".to_string(), Some("group".to_string()))
+            .unwrap();
+        let mut copy = g.clone_graph();
+        assert_eq!(copy.to_json(), g.to_json());
+
+        copy.remove_node(&"b".to_string());
+        copy.update_node("a", |label| label.push('!'));
+        assert_eq!(g.edge_count(), 2);
+        assert!(g.has_node(&"b".to_string()));
+        assert_eq!(g.node_or_default("a"), "");
+        assert_eq!(copy.node_or_default("a"), "!");
+        assert_eq!(copy.parent(&"a".to_string()), Some(&"group".to_string()));
+    }
+
+    fn multigraph(directed: bool) -> Graph<(), (), ()> {
+        Graph::new(Some(GraphOption {
+            directed: Some(directed),
+            multigraph: Some(true),
+            compound: Some(false),
+        }))
+    }
+
+    #[test]
+    fn edge_count_between_counts_parallel_edges() {
+        let (a, b) = ("a".to_string(), "b".to_string());
+        let mut g = multigraph(true);
+        g.set_edge(&a, &b, None, None).unwrap();
+        g.set_edge(&a, &b, None, Some("x".to_string())).unwrap();
+        g.set_edge(&b, &a, None, None).unwrap();
+        assert_eq!(g.edge_count_between("a", "b"), 2);
+        assert_eq!(g.edge_count_between("b", "a"), 1);
+        assert_eq!(g.edge_count_between("a", "missing"), 0);
+
+        let mut g = multigraph(false);
+        g.set_edge(&a, &b, None, None).unwrap();
+        g.set_edge(&b, &a, None, Some("x".to_string())).unwrap();
+        g.set_edge(&a, &a, None, None).unwrap();
+        assert_eq!(g.edge_count_between("a", "b"), 2);
+        assert_eq!(g.edge_count_between("b", "a"), 2);
+        assert_eq!(g.edge_count_between("a", "a"), 1);
+    }
+
+    #[test]
+    fn take_node_returns_the_label_and_drops_edges() {
+        let mut g = labelled(&[("a", "b", "ab"), ("b", "c", "bc")]);
+        g.set_node("b".to_string(), Some("middle".to_string()));
+        assert_eq!(g.take_node("b"), Some("middle".to_string()));
+        assert!(!g.has_node(&"b".to_string()));
+        assert_eq!(g.edge_count(), 0);
+        assert_eq!(g.take_node("b"), None);
+        assert_eq!(g.node_count(), 2);
+    }
+
+    #[test]
+    fn ancestors_and_descendants_walk_the_hierarchy() {
+        let mut g = digraph(&[]);
+        for (child, parent) in [("a", "root"), ("b", "a"), ("c", "a"), ("d", "b")] {
+            g.set_parent(&child.to_string(), Some(parent.to_string()))
+                .unwrap();
+        }
+        assert_eq!(g.ancestors("d"), vec!["b", "a", "root"]);
+        assert!(g.ancestors("root").is_empty());
+        // Preorder: each child is followed by its own subtree.
+        assert_eq!(g.descendants("root"), vec!["a", "b", "d", "c"]);
+        assert!(g.descendants("d").is_empty());
+        assert!(g.descendants("missing").is_empty());
+    }
+
+    #[test]
+    fn degree_counts_a_self_loop_once() {
+        let g = digraph(&[("a", "a"), ("a", "b"), ("c", "a")]);
+        assert_eq!((g.in_degree("a"), g.out_degree("a")), (2, 2));
+        assert_eq!(g.degree("a"), 3);
+        assert_eq!(
+            (g.in_degree("b"), g.out_degree("b"), g.degree("b")),
+            (1, 0, 1)
+        );
+        assert_eq!(g.degree("missing"), 0);
+    }
+
+    #[test]
+    fn map_nodes_and_map_edges_keep_the_structure() {
+        let mut g = labelled(&[("a", "b", "ab"), ("b", "c", "bc")]);
+        g.set_node("a".to_string(), Some("first".to_string()));
+        g.set_parent(&"c".to_string(), Some("group".to_string()))
+            .unwrap();
+
+        let lengths = g.map_nodes(|_, label| label.len());
+        assert_eq!(lengths.node(&"a".to_string()), Some(&5));
+        assert_eq!(lengths.node_count(), g.node_count());
+        assert_eq!(
+            lengths.edge(&"a".to_string(), &"b".to_string(), None),
+            Some(&"ab".to_string())
+        );
+        assert_eq!(lengths.parent(&"c".to_string()), Some(&"group".to_string()));
+
+        let ends = g.map_edges(|e, label| format!("{}:{}{}", label, e.v, e.w));
+        assert_eq!(
+            ends.edge(&"b".to_string(), &"c".to_string(), None),
+            Some(&"bc:bc".to_string())
+        );
+        assert_eq!(ends.node(&"a".to_string()), Some(&"first".to_string()));
+        assert_eq!(ends.edge_count(), 2);
+    }
+
+    #[test]
+    fn set_edges_imports_a_batch() {
+        let mut g = labelled(&[]);
+        let edge = |v: &str, w: &str| {
+            (
+                v.to_string(),
+                w.to_string(),
+                Some(format!("{}{}", v, w)),
+                None,
+            )
+        };
+        g.set_edges(&[edge("a", "b"), edge("b", "c"), edge("a", "c")])
+            .unwrap()
+            .set_edges(&[edge("c", "d")])
+            .unwrap();
+        assert_eq!(g.edge_count(), 4);
+        assert_eq!(g.node_count(), 4);
+        assert_eq!(
+            g.edge(&"a".to_string(), &"c".to_string(), None),
+            Some(&"ac".to_string())
+        );
+
+        // A named edge can't go in a non-multigraph; edges before it stay.
+        let named = (
+            "d".to_string(),
+            "e".to_string(),
+            None,
+            Some("x".to_string()),
+        );
+        assert!(g
+            .set_edges(&[edge("c", "e"), named, edge("e", "f")])
+            .is_err());
+        assert!(g.has_edge(&"c".to_string(), &"e".to_string(), None));
+        assert!(!g.has_edge(&"e".to_string(), &"f".to_string(), None));
+    }
+
+    #[test]
+    fn root_children_and_leaf_nodes_on_two_levels() {
+        let mut g = digraph(&[("a", "b")]);
+        for (child, parent) in [("a", "group"), ("b", "group"), ("c", "other")] {
+            g.set_parent(&child.to_string(), Some(parent.to_string()))
+                .unwrap();
+        }
+        g.set_node("loose".to_string(), None);
+
+        let mut roots = g.root_children();
+        roots.sort();
+        assert_eq!(roots, vec!["group", "loose", "other"]);
+        let mut leaves = g.leaf_nodes();
+        leaves.sort();
+        assert_eq!(leaves, vec!["a", "b", "c", "loose"]);
+
+        let mut flat: Graph<(), (), ()> = Graph::new(Some(GraphOption {
+            directed: Some(true),
+            multigraph: Some(false),
+            compound: Some(false),
+        }));
+        flat.set_edge(&"a".to_string(), &"b".to_string(), None, None)
+            .unwrap();
+        assert_eq!(flat.root_children(), vec!["a", "b"]);
+        assert_eq!(flat.leaf_nodes(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn adjacent_tells_no_neighbors_from_no_node() {
+        let mut g = digraph(&[("a", "b"), ("c", "a"), ("b", "a")]);
+        g.set_node("alone".to_string(), None);
+        assert_eq!(
+            g.adjacent("a"),
+            Some(vec!["c".to_string(), "b".to_string()])
+        );
+        assert_eq!(g.adjacent("alone"), Some(vec![]));
+        assert_eq!(g.adjacent("missing"), None);
+    }
+
+    #[test]
+    fn validate_catches_edges_left_behind() {
+        let mut g = labelled(&[("a", "b", "ab"), ("b", "c", "bc")]);
+        g.set_parent(&"a".to_string(), Some("group".to_string()))
+            .unwrap();
+        assert!(g.validate().is_ok());
+        g.remove_node(&"b".to_string());
+        assert!(g.validate().is_ok());
+
+        // graphlib_rust's remove_node forgets edge names when it drops a
+        // node's edges, so named edges outlive their ends.
+        let mut g = multigraph(true);
+        g.set_edge(
+            &"a".to_string(),
+            &"b".to_string(),
+            None,
+            Some("x".to_string()),
+        )
+        .unwrap();
+        g.remove_node(&"b".to_string());
+        assert_eq!(
+            g.validate(),
+            Err(
+                r#"Edge Edge { v: "a", w: "b", name: Some("x") } references missing node b"#
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn subgraph_keeps_edges_among_the_chosen_nodes() {
+        let mut g = labelled(&[
+            ("a", "b", "ab"),
+            ("b", "c", "bc"),
+            ("c", "d", "cd"),
+            ("d", "e", "de"),
+        ]);
+        g.set_parent(&"b".to_string(), Some("group".to_string()))
+            .unwrap();
+        g.set_parent(&"c".to_string(), Some("group".to_string()))
+            .unwrap();
+
+        let keep: HashSet<String> = ["b", "c", "group"].map(String::from).into();
+        let sub = g.subgraph(&keep);
+        assert_eq!(sub.node_count(), 3);
+        assert_eq!(sub.edge_count(), 1);
+        assert_eq!(
+            sub.edge(&"b".to_string(), &"c".to_string(), None),
+            Some(&"bc".to_string())
+        );
+        assert_eq!(sub.parent(&"b".to_string()), Some(&"group".to_string()));
+
+        // Without its parent, a node moves up to the root.
+        let keep: HashSet<String> = ["b", "c", "missing"].map(String::from).into();
+        let sub = g.subgraph(&keep);
+        assert_eq!(sub.nodes(), vec!["b", "c"]);
+        assert_eq!(sub.parent(&"b".to_string()), None);
+        assert!(sub.validate().is_ok());
+    }
+
+    #[test]
+    fn transpose_swaps_successors_and_predecessors() {
+        let mut g = labelled(&[("a", "b", "ab"), ("a", "c", "ac"), ("c", "b", "cb")]);
+        g.set_parent(&"c".to_string(), Some("group".to_string()))
+            .unwrap();
+        let t = g.transpose();
+        let sorted = |vs: Option<Vec<String>>| {
+            vs.map(|mut vs| {
+                vs.sort();
+                vs
+            })
+        };
+        for v in g.nodes() {
+            assert_eq!(
+                sorted(g.successors(&v)),
+                sorted(t.predecessors(&v)),
+                "{}",
+                v
+            );
+        }
+        assert_eq!(
+            t.edge(&"b".to_string(), &"a".to_string(), None),
+            Some(&"ab".to_string())
+        );
+        assert_eq!(t.parent(&"c".to_string()), Some(&"group".to_string()));
+        assert_eq!(t.transpose().to_json(), g.to_json());
+    }
+
+    #[test]
+    fn filter_edges_drops_named_edges_and_keeps_nodes() {
+        let (a, b, c) = ("a".to_string(), "b".to_string(), "c".to_string());
+        let mut g = multigraph(true);
+        g.set_edge(&a, &b, None, None).unwrap();
+        g.set_edge(&a, &b, None, Some("cross".to_string())).unwrap();
+        g.set_edge(&b, &c, None, Some("cross".to_string())).unwrap();
+        g.set_node("d".to_string(), None);
+
+        let filtered = g.filter_edges(|e| e.name.as_deref() != Some("cross"));
+        assert_eq!(filtered.nodes(), g.nodes());
+        assert_eq!(filtered.edge_count(), 1);
+        assert!(filtered.has_edge(&a, &b, None));
+        assert!(!filtered.has_edge(&a, &b, Some("cross".to_string())));
+    }
+
+    #[test]
+    fn to_dot_lists_nodes_edges_and_clusters() {
+        let mut g = digraph(&[("a", "b"), ("b", "c")]);
+        let dot = g.to_dot(|_, _| String::new());
+        assert!(dot.starts_with("digraph {\n"));
+        for v in ["a", "b", "c"] {
+            assert!(dot.contains(&format!("  \"{}\";\n", v)), "{}", dot);
+        }
+        assert!(dot.contains("\"a\" -> \"b\";"));
+        assert!(dot.contains("\"b\" -> \"c\";"));
+        assert!(!dot.contains("subgraph"));
+
+        g.set_parent(&"b".to_string(), Some("group".to_string()))
+            .unwrap();
+        let dot = g.to_dot(|v, _| format!("label={}", dot_id(v)));
+        assert!(dot.contains("subgraph \"cluster_group\" {"), "{}", dot);
+        assert!(dot.contains("    \"b\" [label=\"b\"];\n"), "{}", dot);
+    }
+
+    #[test]
+    fn has_path_follows_edges_forward() {
+        let g = digraph(&[("a", "b"), ("b", "c"), ("d", "d")]);
+        let has_path = |v: &str, w: &str| g.has_path(v, w);
+        assert!(has_path("a", "c"));
+        assert!(!has_path("c", "a"));
+        assert!(!has_path("a", "d"));
+        // A node only reaches itself through a self-loop.
+        assert!(has_path("d", "d"));
+        assert!(!has_path("a", "a"));
+        assert!(!has_path("a", "missing"));
+        assert!(!has_path("missing", "a"));
+    }
+
+    #[test]
+    fn merge_unions_disjoint_and_overlapping_graphs() {
+        let mut g = labelled(&[("a", "b", "ab")]);
+        g.merge(&labelled(&[("c", "d", "cd")])).unwrap();
+        assert_eq!((g.node_count(), g.edge_count()), (4, 2));
+
+        let mut other = labelled(&[("b", "c", "bc"), ("a", "b", "new")]);
+        other.set_node("a".to_string(), Some("label".to_string()));
+        other
+            .set_parent(&"d".to_string(), Some("group".to_string()))
+            .unwrap();
+        g.merge(&other).unwrap();
+        assert_eq!((g.node_count(), g.edge_count()), (5, 3));
+        assert_eq!(g.node(&"a".to_string()), Some(&"label".to_string()));
+        assert_eq!(
+            g.edge(&"a".to_string(), &"b".to_string(), None),
+            Some(&"new".to_string())
+        );
+        assert_eq!(g.parent(&"d".to_string()), Some(&"group".to_string()));
+
+        // Only graphs of the same kind merge.
+        let mut directed = multigraph(true);
+        assert!(directed.merge(&multigraph(false)).is_err());
+        assert!(directed.merge(&multigraph(true)).is_ok());
+    }
+
+    #[test]
+    fn set_parent_checked_returns_typed_errors() {
+        let mut g = digraph(&[]);
+        g.set_parent_checked("b", Some("a")).unwrap();
+        g.set_parent_checked("c", Some("b")).unwrap();
+        assert_eq!(
+            g.set_parent_checked("a", Some("c")).err(),
+            Some(GraphError::WouldCreateCycle {
+                parent: "c".to_string(),
+                child: "a".to_string(),
+            })
+        );
+        assert!(matches!(
+            g.set_parent_checked("a", Some("a")),
+            Err(GraphError::WouldCreateCycle { .. })
+        ));
+        assert_eq!(g.parent(&"a".to_string()), None);
+        assert_eq!(
+            multigraph(true).set_parent_checked("a", Some("b")).err(),
+            Some(GraphError::NotCompound)
+        );
+        assert_eq!(
+            GraphError::NotCompound.to_string(),
+            "Cannot set parent in a non-compound graph"
+        );
+    }
+
+    #[test]
+    fn update_node_and_node_entry_edit_labels_in_place() {
+        let mut g = labelled(&[]);
+        g.set_node("a".to_string(), Some("card".to_string()));
+        assert!(g.update_node("a", |label| label.push_str(" 320x90")));
+        assert!(!g.update_node("missing", |label| label.clear()));
+        assert_eq!(g.node(&"a".to_string()), Some(&"card 320x90".to_string()));
+        assert!(!g.has_node(&"missing".to_string()));
+
+        g.node_entry("a").and_modify(|label| label.clear());
+        assert_eq!(g.node(&"a".to_string()), Some(&String::new()));
+        g.node_entry("b")
+            .and_modify(|label| label.push('!'))
+            .or_insert("new".to_string())
+            .push('!');
+        assert_eq!(g.node(&"b".to_string()), Some(&"new!".to_string()));
+    }
+
+    #[test]
+    fn clear_empties_the_graph_but_keeps_its_options() {
+        let mut g = labelled(&[("a", "b", "ab")]);
+        g.set_parent(&"a".to_string(), Some("group".to_string()))
+            .unwrap();
+        g.set_default_node_value("default".to_string());
+        g.clear();
+        assert_eq!((g.node_count(), g.edge_count()), (0, 0));
+        assert!(g.is_compound() && g.is_directed() && !g.is_multigraph());
+
+        g.set_node("c".to_string(), None);
+        assert_eq!(g.node(&"c".to_string()), Some(&"default".to_string()));
+
+        // Named edges too, which `remove_node` alone would leave behind.
+        let mut m = multigraph(false);
+        m.set_edge(
+            &"a".to_string(),
+            &"b".to_string(),
+            None,
+            Some("x".to_string()),
+        )
+        .unwrap();
+        m.clear();
+        assert_eq!((m.node_count(), m.edge_count()), (0, 0));
+        assert!(!m.is_compound());
+    }
+
+    #[test]
+    fn subtree_size_counts_nested_children() {
+        let mut g = digraph(&[("a", "b")]);
+        for (v, parent) in [
+            ("inner", "outer"),
+            ("a", "inner"),
+            ("b", "inner"),
+            ("c", "outer"),
+        ] {
+            g.set_parent(&v.to_string(), Some(parent.to_string()))
+                .unwrap();
+        }
+        assert_eq!(g.subtree_size("outer"), 4);
+        assert_eq!(g.subtree_size("outer"), g.descendants("outer").len());
+        assert_eq!(g.subtree_size("inner"), 2);
+        assert_eq!(g.subtree_size("a"), 0);
+        assert_eq!(g.subtree_size("missing"), 0);
+    }
+
+    #[test]
+    fn edge_or_insert_default_reuses_the_label_slot() {
+        let (a, b) = ("a".to_string(), "b".to_string());
+        let mut g = labelled(&[]);
+        g.edge_or_insert_default("a", "b", None)
+            .unwrap()
+            .push_str("minlen");
+        g.edge_or_insert_default("a", "b", None)
+            .unwrap()
+            .push_str("=2");
+        assert_eq!(g.edge_count(), 1);
+        assert_eq!(g.edge(&a, &b, None), Some(&"minlen=2".to_string()));
+
+        // Names need a multigraph, as for `set_edge`.
+        assert!(g.edge_or_insert_default("a", "b", Some("x")).is_err());
+        assert_eq!(g.edge_count(), 1);
+    }
+
+    #[test]
+    fn node_or_default_and_edge_or_default() {
+        let mut g = labelled(&[("a", "b", "ab")]);
+        g.set_node("a".to_string(), Some("label".to_string()));
+        assert_eq!(g.node_or_default("a"), "label");
+        assert_eq!(g.node_or_default("missing"), "");
+
+        assert_eq!(g.edge_or_default("a", "b", None), "ab");
+        assert_eq!(g.edge_or_default("b", "a", None), "");
+        assert_eq!(g.edge_or_default("a", "missing", None), "");
+    }
+
+    #[test]
+    fn default_values_label_new_nodes_and_edges() {
+        let (a, b) = ("a".to_string(), "b".to_string());
+        let mut g = labelled(&[]);
+        g.set_default_node_value("node".to_string())
+            .set_default_edge_value("edge".to_string());
+        g.set_node(a.clone(), None);
+        g.set_edge(&a, &b, None, None).unwrap();
+        assert_eq!(g.node(&a), Some(&"node".to_string()));
+        assert_eq!(g.node(&b), Some(&"node".to_string()));
+        assert_eq!(g.edge(&a, &b, None), Some(&"edge".to_string()));
+
+        g.set_default_node_fn(|v| Some(format!("{}!", v)));
+        g.set_node("c".to_string(), None);
+        assert_eq!(g.node(&"c".to_string()), Some(&"c!".to_string()));
+        // Labels already set stay.
+        g.set_node(a.clone(), None);
+        assert_eq!(g.node(&a), Some(&"node".to_string()));
+    }
+
+    #[test]
+    fn isolated_nodes_skips_connected_ones() {
+        let mut g = digraph(&[("a", "b")]);
+        g.set_node("island".to_string(), None);
+        assert_eq!(g.isolated_nodes(), vec!["island".to_string()]);
+    }
+
+    #[test]
+    fn to_undirected_and_back_counts_edges() {
+        let mut g = digraph(&[("a", "b"), ("b", "a"), ("b", "c")]);
+        g.set_parent(&"c".to_string(), Some("p".to_string()))
+            .unwrap();
+
+        let undirected = g.to_undirected();
+        assert!(!undirected.is_directed());
+        assert_eq!(undirected.edge_count(), 2);
+        assert_eq!(undirected.parent(&"c".to_string()), Some(&"p".to_string()));
+
+        let directed = undirected.to_directed();
+        assert!(directed.is_directed());
+        assert_eq!(directed.edge_count(), 4);
+        assert!(directed.has_edge(&"c".to_string(), &"b".to_string(), None));
+        assert_eq!(directed.parent(&"c".to_string()), Some(&"p".to_string()));
+    }
+}
@@ -1,16 +1,31 @@
-use dagre_rust::{layout, GraphConfig, GraphEdge, GraphNode};
+#![allow(non_snake_case)]
+
+use dagre_rust::layout::util::{intersect_rect, Rect};
+use dagre_rust::{layout, GraphConfig, GraphEdge, GraphEdgePoint, GraphNode};
+use graph::GraphExt;
 use graphlib_rust::{Graph, GraphOption};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 extern crate console_error_panic_hook;
 use std::panic;
 extern crate web_sys;
 
+pub mod algo;
+mod dagre_layout;
+mod dagre_order;
+pub mod graph;
+
 // A macro to provide `println!(..)`-style syntax for `console.log` logging.
+// Native builds (examples, tests) have no console and stay quiet.
 macro_rules! log {
     ( $( $t:tt )* ) => {
+        #[cfg(target_arch = "wasm32")]
         web_sys::console::log_1(&format!( $( $t )* ).into());
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = format_args!( $( $t )* );
     }
 }
 
@@ -34,9 +49,60 @@ pub struct IPoint {
 pub struct AssetLayout {
     pub id: GraphId,
     pub bounds: IBounds,
+    /// The layer the asset was placed in, counting from 0: rows for vertical
+    /// layouts, columns for horizontal ones. `None` for links to assets
+    /// outside the graphed set.
+    pub rank: Option<i32>,
+    /// What the box represents, so the renderer can choose a card style:
+    /// `"asset"`, `"source"`, `"observable_source"` or `"link"`.
+    pub kind: Option<String>,
+    /// Id of the group (as in `GroupLayout::id`) the asset belongs to. `None`
+    /// for ungrouped assets and links.
+    pub group: Option<String>,
+    /// Midpoints of the sides of `bounds`, for renderers that attach edges at
+    /// ports instead of using `AssetLayoutEdge::from` and `to`.
+    #[serde(default)]
+    pub anchors: NodeAnchors,
+}
+
+/// The midpoint of each side of a box.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct NodeAnchors {
+    pub top: IPoint,
+    pub bottom: IPoint,
+    pub left: IPoint,
+    pub right: IPoint,
+}
+
+impl NodeAnchors {
+    pub fn from_bounds(bounds: &IBounds) -> Self {
+        let (center_x, center_y) = (
+            bounds.x + bounds.width / 2.0,
+            bounds.y + bounds.height / 2.0,
+        );
+        NodeAnchors {
+            top: IPoint {
+                x: center_x,
+                y: bounds.y,
+            },
+            bottom: IPoint {
+                x: center_x,
+                y: bounds.y + bounds.height,
+            },
+            left: IPoint {
+                x: bounds.x,
+                y: center_y,
+            },
+            right: IPoint {
+                x: bounds.x + bounds.width,
+                y: center_y,
+            },
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct GroupLayout {
     pub id: GraphId,
     pub groupName: String,
@@ -44,6 +110,15 @@ pub struct GroupLayout {
     pub repositoryLocationName: String,
     pub repositoryDisambiguationRequired: bool,
     pub bounds: IBounds,
+    /// The strip of `bounds` that holds the group name and collapse toggle:
+    /// across the top, the full width of the box and `group_padding.y` tall.
+    /// In horizontal layouts it runs down the left side instead, the full
+    /// height of the box and `group_padding.y` wide.
+    pub header_bounds: IBounds,
+    /// Ids of the assets in the group, sorted. A collapsed group lists the
+    /// members its box stands in for, which have no layout of their own.
+    #[serde(default)]
+    pub member_ids: Vec<GraphId>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
@@ -52,14 +127,586 @@ pub struct AssetLayoutEdge {
     pub fromId: GraphId,
     pub to: IPoint,
     pub toId: GraphId,
+    /// The full poly-line: `from`, any bend points dagre routed the edge
+    /// through, then `to`.
+    pub points: Vec<IPoint>,
+    /// Centre of the edge's label, for edges given one in
+    /// `GraphData::edgeLabels`.
+    pub label: Option<IPoint>,
+    /// Whether the edge was laid out in the opposite direction to break a
+    /// cycle, so it runs against the flow of the ranks. `from`, `to` and
+    /// `points` still go from `fromId` to `toId`, so the arrowhead belongs
+    /// at `to` either way.
+    #[serde(default)]
+    pub reversed: bool,
 }
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct AssetGraphLayout {
     pub width: i32,
     pub height: i32,
+    /// Sorted by `fromId`, then `toId`, so the order doesn't depend on how
+    /// the input maps were iterated.
     pub edges: Vec<AssetLayoutEdge>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub nodes: HashMap<GraphId, AssetLayout>,
+    /// The ids in `nodes`, rank by rank in the direction the graph flows and
+    /// in order along each rank, for renderers that list the graph. Packed
+    /// components are listed one after the other.
+    #[serde(default)]
+    pub ordered_node_ids: Vec<GraphId>,
+    #[serde(serialize_with = "serialize_sorted")]
     pub groups: HashMap<String, GroupLayout>,
+    /// Ids in `nodes` that are link stubs for assets outside the graphed set
+    /// rather than asset cards, sorted.
+    #[serde(default)]
+    pub external_links: Vec<GraphId>,
+    /// How many times edges cross each other as drawn, for comparing layouts
+    /// of the same graph. Self-loops and edges meeting at a shared endpoint
+    /// don't count. Only counted with
+    /// `LayoutAssetGraphOptions::count_edge_crossings`.
+    #[serde(default)]
+    pub edge_crossings: Option<usize>,
+    /// `LayoutAssetGraphOptions::meta`, passed through unchanged.
+    #[serde(default)]
+    pub meta: Option<String>,
+}
+
+/// `AssetGraphLayout` in the array shapes React Flow takes for its `nodes`
+/// and `edges` props.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ReactFlowLayout {
+    pub nodes: Vec<ReactFlowNode>,
+    pub edges: Vec<ReactFlowEdge>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ReactFlowNode {
+    pub id: GraphId,
+    /// Top-left corner of the node's bounds.
+    pub position: IPoint,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ReactFlowEdge {
+    /// `"<source>-><target>"`.
+    pub id: String,
+    pub source: GraphId,
+    pub target: GraphId,
+}
+
+/// What changed between two layouts, from `AssetGraphLayout::diff`. Edges are
+/// identified by `(fromId, toId)`. Every list is sorted.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LayoutDiff {
+    pub added: Vec<GraphId>,
+    pub removed: Vec<GraphId>,
+    pub moved: Vec<MovedNode>,
+    pub added_edges: Vec<(GraphId, GraphId)>,
+    pub removed_edges: Vec<(GraphId, GraphId)>,
+    /// Edges in both layouts whose route or label position changed.
+    pub changed_edges: Vec<(GraphId, GraphId)>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct MovedNode {
+    pub id: GraphId,
+    pub old_bounds: IBounds,
+    pub new_bounds: IBounds,
+}
+
+impl AssetGraphLayout {
+    /// The layout as React Flow nodes and edges. Nodes follow
+    /// `ordered_node_ids`. Edge routes aren't carried over; React Flow draws
+    /// its own.
+    pub fn to_react_flow(&self) -> ReactFlowLayout {
+        let nodes = self
+            .ordered_node_ids
+            .iter()
+            .filter_map(|id| self.nodes.get(id))
+            .map(|node| ReactFlowNode {
+                id: node.id.clone(),
+                position: IPoint {
+                    x: node.bounds.x,
+                    y: node.bounds.y,
+                },
+                width: node.bounds.width,
+                height: node.bounds.height,
+            })
+            .collect();
+        let edges = self
+            .edges
+            .iter()
+            .map(|edge| ReactFlowEdge {
+                id: format!("{}->{}", edge.fromId, edge.toId),
+                source: edge.fromId.clone(),
+                target: edge.toId.clone(),
+            })
+            .collect();
+        ReactFlowLayout { nodes, edges }
+    }
+
+    /// Scales the whole layout about its top-left corner so `width` and
+    /// `height` fit within `max_width` by `max_height`, keeping the aspect
+    /// ratio, e.g. for thumbnails. Layouts smaller than the box are scaled
+    /// up. Returns the scale used, which is 1 for an empty layout.
+    pub fn fit_to(&mut self, max_width: f32, max_height: f32) -> f32 {
+        if self.width <= 0 || self.height <= 0 {
+            return 1.0;
+        }
+        let scale = f32::min(
+            max_width / self.width as f32,
+            max_height / self.height as f32,
+        );
+        let scale_point = |p: &mut IPoint| {
+            p.x *= scale;
+            p.y *= scale;
+        };
+        let scale_bounds = |b: &mut IBounds| {
+            b.x *= scale;
+            b.y *= scale;
+            b.width *= scale;
+            b.height *= scale;
+        };
+
+        self.width = (self.width as f32 * scale).round() as i32;
+        self.height = (self.height as f32 * scale).round() as i32;
+        for node in self.nodes.values_mut() {
+            scale_bounds(&mut node.bounds);
+            node.anchors = NodeAnchors::from_bounds(&node.bounds);
+        }
+        for edge in &mut self.edges {
+            scale_point(&mut edge.from);
+            scale_point(&mut edge.to);
+            edge.points.iter_mut().for_each(scale_point);
+            edge.label.iter_mut().for_each(scale_point);
+        }
+        for group in self.groups.values_mut() {
+            scale_bounds(&mut group.bounds);
+            scale_bounds(&mut group.header_bounds);
+        }
+        scale
+    }
+
+    /// The nodes and edges that were added, removed or moved going from this
+    /// layout to `other`, for animating between them. Differences under half
+    /// a pixel are treated as float noise.
+    pub fn diff(&self, other: &AssetGraphLayout) -> LayoutDiff {
+        const EPSILON: f32 = 0.5;
+        let near = |a: f32, b: f32| (a - b).abs() <= EPSILON;
+        let same_point = |a: &IPoint, b: &IPoint| near(a.x, b.x) && near(a.y, b.y);
+        let same_bounds = |a: &IBounds, b: &IBounds| {
+            near(a.x, b.x) && near(a.y, b.y) && near(a.width, b.width) && near(a.height, b.height)
+        };
+
+        let mut diff = LayoutDiff::default();
+        for (id, node) in &self.nodes {
+            match other.nodes.get(id) {
+                None => diff.removed.push(id.clone()),
+                Some(new) if !same_bounds(&node.bounds, &new.bounds) => {
+                    diff.moved.push(MovedNode {
+                        id: id.clone(),
+                        old_bounds: node.bounds.clone(),
+                        new_bounds: new.bounds.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        diff.added = other
+            .nodes
+            .keys()
+            .filter(|id| !self.nodes.contains_key(*id))
+            .cloned()
+            .collect();
+
+        let edges_by_key =
+            |layout: &AssetGraphLayout| -> HashMap<(GraphId, GraphId), AssetLayoutEdge> {
+                layout
+                    .edges
+                    .iter()
+                    .map(|edge| ((edge.fromId.clone(), edge.toId.clone()), edge.clone()))
+                    .collect()
+            };
+        let (old_edges, new_edges) = (edges_by_key(self), edges_by_key(other));
+        for (key, edge) in &old_edges {
+            let Some(new) = new_edges.get(key) else {
+                diff.removed_edges.push(key.clone());
+                continue;
+            };
+            let same_route = edge.points.len() == new.points.len()
+                && edge
+                    .points
+                    .iter()
+                    .zip(&new.points)
+                    .all(|(a, b)| same_point(a, b));
+            let same_label = match (&edge.label, &new.label) {
+                (Some(a), Some(b)) => same_point(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            };
+            if !same_route || !same_label {
+                diff.changed_edges.push(key.clone());
+            }
+        }
+        diff.added_edges = new_edges
+            .keys()
+            .filter(|key| !old_edges.contains_key(*key))
+            .cloned()
+            .collect();
+
+        diff.added.sort();
+        diff.removed.sort();
+        diff.moved.sort_by(|a, b| a.id.cmp(&b.id));
+        diff.added_edges.sort();
+        diff.removed_edges.sort();
+        diff.changed_edges.sort();
+        diff
+    }
+
+    /// Updates the layout for assets added to or removed from `graph_data`
+    /// without running dagre again, for interactive editing of large graphs.
+    ///
+    /// Removed ids lose their node and every edge touching them. Each added
+    /// asset is sized with `get_asset_node_dimensions` and placed one rank
+    /// past its upstream neighbors in the layout (or before its downstream
+    /// ones), centred across from them and slid sideways off any card it
+    /// would overlap. Its edges are drawn as straight lines. If that puts it
+    /// above or left of the layout, everything shifts to make room.
+    ///
+    /// This is a heuristic: existing nodes stay where they are, crossings
+    /// aren't re-optimized, and group bounds aren't recomputed. Run a full
+    /// layout once editing settles. `edge_crossings` is recounted if the
+    /// layout has it.
+    pub fn apply_delta(
+        &mut self,
+        added: &[AssetGraphNode],
+        removed: &[GraphId],
+        graph_data: &GraphData,
+    ) {
+        let removed: HashSet<&GraphId> = removed.iter().collect();
+        self.nodes.retain(|id, _| !removed.contains(id));
+        self.ordered_node_ids.retain(|id| !removed.contains(id));
+        self.external_links.retain(|id| !removed.contains(id));
+        for group in self.groups.values_mut() {
+            group.member_ids.retain(|id| !removed.contains(id));
+        }
+        self.edges
+            .retain(|edge| !removed.contains(&edge.fromId) && !removed.contains(&edge.toId));
+
+        let flow = self.flow();
+        for node in added {
+            if self.nodes.contains_key(&node.id) {
+                continue;
+            }
+            let mut upstream: Vec<GraphId> = vec![];
+            let mut downstream: Vec<GraphId> = vec![];
+            for (upstream_id, downstream_id) in dependency_pairs(graph_data) {
+                if downstream_id == &node.id && self.nodes.contains_key(upstream_id) {
+                    upstream.push(upstream_id.clone());
+                } else if upstream_id == &node.id && self.nodes.contains_key(downstream_id) {
+                    downstream.push(downstream_id.clone());
+                }
+            }
+            upstream.dedup();
+            downstream.dedup();
+
+            let size = get_asset_node_dimensions(&node.definition);
+            let bounds = self.place(&flow, &size, &upstream, &downstream);
+            let ranks = |ids: &[GraphId]| -> Vec<i32> {
+                ids.iter().filter_map(|id| self.nodes[id].rank).collect()
+            };
+            let mut rank = match (
+                ranks(&upstream).into_iter().max(),
+                ranks(&downstream).into_iter().min(),
+            ) {
+                (Some(rank), _) => Some(rank + 1),
+                (None, Some(rank)) => Some(rank - 1),
+                (None, None) => None,
+            };
+            if rank == Some(-1) {
+                for layout in self.nodes.values_mut() {
+                    layout.rank = layout.rank.map(|rank| rank + 1);
+                }
+                rank = Some(0);
+            }
+
+            self.width = self.width.max((bounds.x + bounds.width).round() as i32);
+            self.height = self.height.max((bounds.y + bounds.height).round() as i32);
+            let index = match rank {
+                Some(rank) => self
+                    .ordered_node_ids
+                    .iter()
+                    .position(|id| self.nodes[id].rank.is_some_and(|other| other > rank))
+                    .unwrap_or(self.ordered_node_ids.len()),
+                None => self.ordered_node_ids.len(),
+            };
+            self.ordered_node_ids.insert(index, node.id.clone());
+            let group = group_for_node(node);
+            if let Some(group) = group.as_ref().and_then(|id| self.groups.get_mut(id)) {
+                let index = group.member_ids.partition_point(|id| id < &node.id);
+                group.member_ids.insert(index, node.id.clone());
+            }
+            self.nodes.insert(
+                node.id.clone(),
+                AssetLayout {
+                    id: node.id.clone(),
+                    anchors: NodeAnchors::from_bounds(&bounds),
+                    bounds,
+                    rank,
+                    kind: Some(asset_layout_kind(Some(node), false)),
+                    group,
+                },
+            );
+
+            for (from, to) in upstream
+                .iter()
+                .map(|id| (id, &node.id))
+                .chain(downstream.iter().map(|id| (&node.id, id)))
+            {
+                let (from_bounds, to_bounds) = (&self.nodes[from].bounds, &self.nodes[to].bounds);
+                let from_point = flow.exit(from_bounds);
+                let to_point = flow.entry(to_bounds);
+                self.edges.push(AssetLayoutEdge {
+                    from: from_point.clone(),
+                    fromId: from.clone(),
+                    to: to_point.clone(),
+                    toId: to.clone(),
+                    points: vec![from_point, to_point],
+                    label: None,
+                    reversed: false,
+                });
+            }
+        }
+        sort_edges(&mut self.edges);
+
+        if self.edge_crossings.is_some() {
+            self.edge_crossings = Some(count_edge_crossings(&self.edges));
+        }
+
+        // Nodes placed before the first rank can land at negative coordinates,
+        // so move everything back into view.
+        let (min_x, min_y) = self.nodes.values().fold((0.0, 0.0), |(x, y), n| {
+            (f32::min(x, n.bounds.x), f32::min(y, n.bounds.y))
+        });
+        if min_x < 0.0 || min_y < 0.0 {
+            let (dx, dy) = (-min_x, -min_y);
+            let mut shifted = AssetGraphLayout {
+                width: self.width + dx.round() as i32,
+                height: self.height + dy.round() as i32,
+                meta: self.meta.clone(),
+                ..Default::default()
+            };
+            merge_translated_layout(&mut shifted, std::mem::take(self), dx, dy);
+            *self = shifted;
+        }
+    }
+
+    // Which way ranks advance. dagre centres every node of a rank on the same
+    // line, so a rank with two nodes gives the axis; otherwise it's whichever
+    // axis the first and last ranks are further apart on. Defaults to top to
+    // bottom with fewer than two ranks.
+    fn flow(&self) -> Flow {
+        let mut by_rank: BTreeMap<i32, Vec<IPoint>> = BTreeMap::new();
+        for layout in self.nodes.values() {
+            if let Some(rank) = layout.rank {
+                let b = &layout.bounds;
+                by_rank.entry(rank).or_default().push(IPoint {
+                    x: b.x + b.width / 2.0,
+                    y: b.y + b.height / 2.0,
+                });
+            }
+        }
+        let (Some(first), Some(last)) = (by_rank.values().next(), by_rank.values().last()) else {
+            return Flow {
+                horizontal: false,
+                sign: 1.0,
+            };
+        };
+        let mean = |centers: &Vec<IPoint>| IPoint {
+            x: centers.iter().map(|c| c.x).sum::<f32>() / centers.len() as f32,
+            y: centers.iter().map(|c| c.y).sum::<f32>() / centers.len() as f32,
+        };
+        let (start, end) = (mean(first), mean(last));
+        let horizontal = match by_rank.values().find(|centers| centers.len() > 1) {
+            Some(centers) => centers.iter().all(|c| (c.x - centers[0].x).abs() < 0.5),
+            None => (end.x - start.x).abs() > (end.y - start.y).abs(),
+        };
+        let forward = if horizontal {
+            end.x - start.x
+        } else {
+            end.y - start.y
+        };
+        Flow {
+            horizontal,
+            sign: if forward < 0.0 { -1.0 } else { 1.0 },
+        }
+    }
+
+    // Bounds for a new node of `size`, next to its neighbors and clear of
+    // every existing node.
+    fn place(
+        &self,
+        flow: &Flow,
+        size: &IBounds,
+        upstream: &[GraphId],
+        downstream: &[GraphId],
+    ) -> IBounds {
+        // Gap between ranks and between nodes in a rank, as in the default
+        // ranksep and nodesep.
+        const SPACING: f32 = 50.0;
+
+        let bounds_of = |ids: &[GraphId]| -> Vec<IBounds> {
+            ids.iter().map(|id| self.nodes[id].bounds.clone()).collect()
+        };
+        let (neighbors, main) = if !upstream.is_empty() {
+            let neighbors = bounds_of(upstream);
+            let far_side = neighbors
+                .iter()
+                .map(|b| flow.forward(b) + flow.half_extent(b))
+                .fold(f32::MIN, f32::max);
+            (neighbors, far_side + SPACING + flow.half_extent(size))
+        } else if !downstream.is_empty() {
+            let neighbors = bounds_of(downstream);
+            let near_side = neighbors
+                .iter()
+                .map(|b| flow.forward(b) - flow.half_extent(b))
+                .fold(f32::MAX, f32::min);
+            (neighbors, near_side - SPACING - flow.half_extent(size))
+        } else {
+            let all: Vec<IBounds> = self.nodes.values().map(|n| n.bounds.clone()).collect();
+            let far_side = all
+                .iter()
+                .map(|b| flow.forward(b) + flow.half_extent(b))
+                .fold(0.0, f32::max);
+            (vec![], far_side + SPACING + flow.half_extent(size))
+        };
+        let mut across = if neighbors.is_empty() {
+            flow.cross_half_extent(size)
+        } else {
+            neighbors.iter().map(|b| flow.across(b)).sum::<f32>() / neighbors.len() as f32
+        };
+
+        let mut bounds = flow.bounds_at(main, across, size);
+        while let Some(blocker) = self
+            .nodes
+            .values()
+            .map(|n| &n.bounds)
+            .find(|other| overlaps(&bounds, other, SPACING / 2.0))
+        {
+            across = flow.across(blocker)
+                + flow.cross_half_extent(blocker)
+                + SPACING
+                + flow.cross_half_extent(size);
+            bounds = flow.bounds_at(main, across, size);
+        }
+        bounds
+    }
+}
+
+// The direction ranks advance in a laid out graph: along x or y, towards
+// larger or smaller coordinates.
+struct Flow {
+    horizontal: bool,
+    sign: f32,
+}
+
+impl Flow {
+    // Centre of `b` along the flow, signed so that later ranks are larger.
+    fn forward(&self, b: &IBounds) -> f32 {
+        let center = if self.horizontal {
+            b.x + b.width / 2.0
+        } else {
+            b.y + b.height / 2.0
+        };
+        center * self.sign
+    }
+
+    // Centre of `b` across the flow.
+    fn across(&self, b: &IBounds) -> f32 {
+        if self.horizontal {
+            b.y + b.height / 2.0
+        } else {
+            b.x + b.width / 2.0
+        }
+    }
+
+    fn half_extent(&self, b: &IBounds) -> f32 {
+        if self.horizontal {
+            b.width / 2.0
+        } else {
+            b.height / 2.0
+        }
+    }
+
+    fn cross_half_extent(&self, b: &IBounds) -> f32 {
+        if self.horizontal {
+            b.height / 2.0
+        } else {
+            b.width / 2.0
+        }
+    }
+
+    // Bounds of `size` centred at `forward` along the flow and `across` it.
+    fn bounds_at(&self, forward: f32, across: f32, size: &IBounds) -> IBounds {
+        let main = forward * self.sign;
+        let (cx, cy) = if self.horizontal {
+            (main, across)
+        } else {
+            (across, main)
+        };
+        IBounds {
+            x: cx - size.width / 2.0,
+            y: cy - size.height / 2.0,
+            width: size.width,
+            height: size.height,
+        }
+    }
+
+    // Where an edge leaves `b`: the middle of its downstream side.
+    fn exit(&self, b: &IBounds) -> IPoint {
+        self.side_point(b, 1.0)
+    }
+
+    // Where an edge reaches `b`: the middle of its upstream side.
+    fn entry(&self, b: &IBounds) -> IPoint {
+        self.side_point(b, -1.0)
+    }
+
+    fn side_point(&self, b: &IBounds, side: f32) -> IPoint {
+        let offset = side * self.sign;
+        if self.horizontal {
+            IPoint {
+                x: b.x + b.width / 2.0 + offset * b.width / 2.0,
+                y: b.y + b.height / 2.0,
+            }
+        } else {
+            IPoint {
+                x: b.x + b.width / 2.0,
+                y: b.y + b.height / 2.0 + offset * b.height / 2.0,
+            }
+        }
+    }
+}
+
+// Whether `a` and `b` come within `margin` of each other.
+fn overlaps(a: &IBounds, b: &IBounds, margin: f32) -> bool {
+    a.x < b.x + b.width + margin
+        && b.x < a.x + a.width + margin
+        && a.y < b.y + b.height + margin
+        && b.y < a.y + a.height + margin
+}
+
+// Writes maps in key order so the same layout always serializes identically.
+fn serialize_sorted<S, V>(map: &HashMap<String, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: Serialize,
+{
+    map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
@@ -67,6 +714,50 @@ pub struct GraphData {
     pub nodes: HashMap<GraphId, AssetGraphNode>,
     pub downstream: HashMap<GraphId, HashMap<GraphId, bool>>,
     pub upstream: HashMap<GraphId, HashMap<GraphId, bool>>,
+    /// Optional dagre hints for individual dependencies, keyed like
+    /// `downstream`: upstream id, then downstream id.
+    #[serde(default)]
+    pub edgeHints: HashMap<GraphId, HashMap<GraphId, EdgeHints>>,
+    /// Optional label sizes for individual dependencies, keyed like
+    /// `downstream`. dagre reserves room for each label between the ranks.
+    #[serde(default)]
+    pub edgeLabels: HashMap<GraphId, HashMap<GraphId, EdgeLabel>>,
+}
+
+impl GraphData {
+    /// Deserializes straight from `reader` without reading it into a string
+    /// first. Reads are unbuffered, so wrap files in a `BufReader`.
+    pub fn from_reader(reader: impl std::io::Read) -> Result<GraphData, serde_json::Error> {
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let graph_data = GraphData::deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        Ok(graph_data)
+    }
+}
+
+/// Per-edge ranking hints passed through to dagre.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct EdgeHints {
+    /// Minimum number of ranks between the two ends.
+    pub minlen: i32,
+    /// How strongly dagre tries to keep the edge short and straight.
+    pub weight: i32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct EdgeLabel {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for EdgeHints {
+    fn default() -> Self {
+        Self {
+            minlen: 1,
+            weight: 1,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
@@ -87,6 +778,10 @@ pub struct AssetNode {
     pub description: Option<String>,
     pub computeKind: Option<String>,
     pub isPartitioned: bool,
+    /// How many partition dimensions a partitioned asset has, each of which
+    /// gets its own row on the card. When unset, partitioned cards get room
+    /// for two.
+    pub partitionDimensions: Option<usize>,
     pub isObservable: bool,
     pub isSource: bool,
     pub repository: Repository,
@@ -116,324 +811,3723 @@ pub struct AssetGraphNode {
     pub definition: AssetNode,
 }
 const GROUP_NODE_PREFIX: &str = "group__";
-const MARGIN: i32 = 100;
 
-#[derive(Deserialize, Serialize, Debug, Clone, Default)]
-pub struct LayoutAssetGraphOptions {
-    pub horizontalDAGs: bool,
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankDir {
+    TB,
+    BT,
+    LR,
+    RL,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Default)]
-pub struct LayoutAssetGraphArgs {
-    pub graphData: GraphData,
-    pub opts: LayoutAssetGraphOptions,
-}
+impl RankDir {
+    pub fn is_horizontal(&self) -> bool {
+        matches!(self, RankDir::LR | RankDir::RL)
+    }
 
-#[wasm_bindgen]
-pub fn layout_asset_graph(args: &str) -> String {
-    panic::set_hook(Box::new(console_error_panic_hook::hook));
-    let args_json: Result<LayoutAssetGraphArgs, serde_json::Error> = serde_json::from_str(args);
-    let mut args = LayoutAssetGraphArgs::default();
-    match args_json {
-        Ok(resolved_args) => args = resolved_args,
-        Err(err) => {
-            // Handle the error, for example, print the error message.
-            eprintln!("Error: {}", err);
+    fn to_dagre(self) -> String {
+        match self {
+            RankDir::TB => "tb",
+            RankDir::BT => "bt",
+            RankDir::LR => "lr",
+            RankDir::RL => "rl",
         }
+        .to_string()
     }
-    let graph_data = args.graphData;
-    let opts = args.opts;
-
-    let mut g: Graph<GraphConfig, GraphNode, GraphEdge> =
-        graphlib_rust::graph::Graph::new(Some(GraphOption {
-            compound: Some(true),
-            directed: Some(false),
-            multigraph: Some(false),
-        }));
-
-    let mut nodes: HashMap<GraphId, AssetLayout> = HashMap::new();
-    let mut groups: HashMap<String, GroupLayout> = HashMap::new();
-    let mut links_to_assets_outside_graphed_set: HashMap<GraphId, bool> = HashMap::new();
+}
 
-    let parent_node_id_for_node = |node: &AssetGraphNode| {
-        format!(
-            "{}__{}__{}__{}",
-            GROUP_NODE_PREFIX,
-            node.definition.repository.location.name,
-            node.definition.repository.name,
-            node.definition.groupName.clone().unwrap_or_default()
-        )
-    };
+/// One of dagre's four node alignments. dagre positions each node within its
+/// rank four ways, lining it up with its upstream (`U`) or downstream (`D`)
+/// neighbours and packing blocks to the left (`L`) or right (`R`), then
+/// normally takes the average. Naming one uses it alone, e.g. `UL` to keep
+/// long chains straight along the left.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    UL,
+    UR,
+    DL,
+    DR,
+}
 
-    let should_render = |node: Option<&AssetGraphNode>| -> bool {
-        if let Some(node) = node {
-            node.definition.opNames.len() > 0
-        } else {
-            false
+impl Align {
+    fn to_dagre(self) -> String {
+        match self {
+            Align::UL => "ul",
+            Align::UR => "ur",
+            Align::DL => "dl",
+            Align::DR => "dr",
         }
-    };
+        .to_string()
+    }
+}
 
-    let rendered_nodes: Vec<&AssetGraphNode> = graph_data
-        .nodes
-        .values()
-        .filter(|node| should_render(Some(node)))
-        .collect();
+/// How far in from each side of an asset's box its edges attach. Only the
+/// sides edges enter and leave by are used: in vertical layouts, `top` and
+/// `bottom` set the height of the attachment points and `left` how far along
+/// the card they sit; in horizontal layouts, `left` and `right` set how far
+/// into the card edges reach, centred vertically.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EdgeInsets {
+    pub top: f32,
+    pub bottom: f32,
+    pub left: f32,
+    pub right: f32,
+}
 
-    for node in &rendered_nodes {
-        if let Some(group_name) = &node.definition.groupName {
-            let id = parent_node_id_for_node(node);
-            groups.insert(
-                id.clone(),
-                GroupLayout {
-                    id: id.clone(),
-                    groupName: group_name.clone(),
-                    repositoryName: node.definition.repository.name.clone(),
-                    repositoryLocationName: node.definition.repository.location.name.clone(),
-                    repositoryDisambiguationRequired: false,
-                    bounds: IBounds {
-                        x: 0.0,
-                        y: 0.0,
-                        width: 0.0,
-                        height: 0.0,
-                    },
-                },
-            );
+impl EdgeInsets {
+    /// The insets used when `edge_insets` is unset. An asset's box includes
+    /// the tag rows drawn above and below its card, so in TB edges leave 30px
+    /// up from the bottom (above the tags beneath) and arrive 20px down from
+    /// the top (below the tags above), where the card itself starts and ends,
+    /// and attach 24px in from the left rather than at the centre. BT mirrors
+    /// this. Horizontal layouts attach at the box edges.
+    pub fn default_for(rankdir: RankDir) -> Self {
+        match rankdir {
+            RankDir::TB => EdgeInsets {
+                top: 20.0,
+                bottom: 30.0,
+                left: 24.0,
+                right: 0.0,
+            },
+            RankDir::BT => EdgeInsets {
+                top: 30.0,
+                bottom: 20.0,
+                left: 24.0,
+                right: 0.0,
+            },
+            RankDir::LR | RankDir::RL => EdgeInsets {
+                top: 0.0,
+                bottom: 0.0,
+                left: 0.0,
+                right: 0.0,
+            },
         }
     }
+}
 
-    let show_groups = groups.len() > 1;
-    if show_groups {
-        for group_id in groups.keys() {
-            g.set_node(group_id.clone(), Some(GraphNode::default()));
-        }
-    }
+/// How cycles are broken before ranking.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Acyclicer {
+    /// Reverse a greedy feedback arc set (`algo::greedy_fas`) before handing
+    /// the graph to dagre. Tends to reverse fewer edges, at the cost of an
+    /// extra pass over the graph.
+    #[default]
+    Greedy,
+    /// Reverse the back edges of a depth-first search (`algo::dfs_fas`), as
+    /// dagre does by default. Cheaper, but can reverse more edges than needed
+    /// depending on where the search starts.
+    Dfs,
+}
 
-    for node in &rendered_nodes {
-        let asset_node_dimensions = get_asset_node_dimensions(&node.definition);
-        let mut g_node = GraphNode::default();
-        g_node.width = asset_node_dimensions.width;
-        g_node.height = asset_node_dimensions.height;
-        g.set_node(node.id.clone(), Some(g_node));
+/// Where edges start and end on the nodes they join.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeEndpointStyle {
+    /// On the sides of the cards facing along the flow, moved in by
+    /// `edge_insets`, with `arrow_gap` left before the target.
+    #[default]
+    Inset,
+    /// At the centre of each node, for renderers that clip edges to the
+    /// cards themselves. `edge_insets` and `arrow_gap` are ignored.
+    Center,
+}
 
-        if show_groups && node.definition.groupName.is_some() {
-            g.set_parent(&node.id, Some(parent_node_id_for_node(node)))
-                .unwrap_throw();
-        }
-    }
+#[derive(Clone)]
+pub struct NodeDimensionsFn(pub Rc<dyn Fn(&AssetNode) -> IBounds>);
 
-    for (upstream_id, graph_data_downstream) in &graph_data.downstream {
-        for downstream_id in graph_data_downstream.keys() {
-            if !should_render(graph_data.nodes.get(downstream_id))
-                && !should_render(graph_data.nodes.get(upstream_id))
-            {
-                continue;
-            }
+impl fmt::Debug for NodeDimensionsFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NodeDimensionsFn")
+    }
+}
 
-            g.set_edge(upstream_id, downstream_id, None, None)
-                .unwrap_throw();
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LayoutAssetGraphOptions {
+    /// Deprecated: use `rankdir`. When `rankdir` is unset, `true` maps to `LR`
+    /// and `false` to `TB`.
+    pub horizontalDAGs: bool,
+    pub rankdir: Option<RankDir>,
+    pub nodesep: f32,
+    pub ranksep: f32,
+    pub edgesep: f32,
+    /// Extra space added to the right and bottom of the layout.
+    pub margin: i32,
+    /// Space between a group's members and its box. The larger `y` leaves room
+    /// for the group header label, so it goes on the top and bottom. In
+    /// horizontal layouts the header sits on the left, so `y` is the left
+    /// padding and `x` is used for the other three sides.
+    pub group_padding: IPoint,
+    /// Overrides `get_asset_node_dimensions` for sizing asset cards. Only
+    /// available to Rust callers, since it can't be passed through JSON.
+    #[serde(skip)]
+    pub node_dimensions_fn: Option<NodeDimensionsFn>,
+    /// Ids of groups (as in `GroupLayout::id`) to draw as a single box in place
+    /// of their member assets.
+    pub collapsed_groups: HashSet<String>,
+    /// Whether assets that depend on themselves get a loop edge.
+    pub self_loops: bool,
+    /// Lay out disconnected parts of the graph separately and pack them
+    /// together, instead of letting dagre line them all up side by side.
+    pub pack_components: bool,
+    /// Characters of a link stub's label that count towards its width in
+    /// horizontal layouts.
+    pub link_name_max_length: usize,
+    /// Characters of an asset name shown on its card before truncating. Cards
+    /// are a fixed width, so this is for the renderer and doesn't affect the
+    /// layout.
+    pub node_name_max_length: usize,
+    /// Height of a link stub.
+    pub link_height: f32,
+    /// Caller-chosen ordering within a rank. Hinted assets that share a rank
+    /// are placed in increasing hint order. dagre can't take a starting order,
+    /// so this runs after layout and swaps them between the slots they
+    /// already occupied. Unhinted assets stay where dagre put them.
+    pub order_hint: HashMap<GraphId, f32>,
+    /// Space left between the end of an edge and the card it points at in
+    /// horizontal layouts, so the arrowhead doesn't overlap the card.
+    pub arrow_gap: f32,
+    /// Ids of groups (as in `GroupLayout::id`) in the order they should be
+    /// placed along the rank direction's cross axis. Groups and their
+    /// members go into dagre in this order, which dagre's initial ordering
+    /// follows unless it can reduce crossings otherwise. Unlisted groups come
+    /// after, by id.
+    pub group_order: Vec<String>,
+    pub acyclicer: Acyclicer,
+    /// Ranks to place assets on, such as 0 for every source. Only relative
+    /// values matter: assets given the same rank line up, and each step
+    /// between overrides is one rank apart. Layout fails with
+    /// `LayoutError::RankConflict` if the edges leave no room for an
+    /// override. Assets inside a collapsed group are ignored.
+    pub rank_overrides: HashMap<GraphId, i32>,
+    /// Most assets to lay out. Larger graphs fail with
+    /// `LayoutError::TooLarge` before dagre runs, since dagre can take long
+    /// enough on them to freeze the page.
+    pub max_nodes: Option<usize>,
+    /// Where edges attach to asset cards. Defaults to
+    /// `EdgeInsets::default_for(rankdir)`. Link stubs use the same insets,
+    /// except that in vertical layouts they're narrower and attach 16px in
+    /// from the left.
+    pub edge_insets: Option<EdgeInsets>,
+    /// Leave out non-observable source assets entirely, along with their
+    /// dependencies, rather than drawing them or link stubs for them.
+    pub hide_sources: bool,
+    /// Grid size in pixels to snap the layout to. Each node's top-left
+    /// corner, and every edge point and label, is rounded to the nearest
+    /// multiple of it. Sizes are kept. Edge endpoints are rounded towards
+    /// the middle of their card, so they still meet it.
+    pub snap_grid: Option<f32>,
+    /// Bend edges around the boxes of groups they pass through but don't
+    /// start or end in, instead of drawing them over the group. Only applies
+    /// when group boxes are drawn.
+    pub route_around_groups: bool,
+    /// Close up rows (columns in horizontal layouts) that dagre leaves
+    /// without any assets, such as the ranks skipped by `rank_overrides`.
+    /// Gaps wider than `ranksep`, the space dagre leaves between neighbouring
+    /// ranks, shrink to it and everything past them moves back. Group boxes
+    /// keep their padding, and nothing changes order.
+    pub compact: bool,
+    /// Caller's own label for the layout, such as the id of the view it's
+    /// for. Not used by the layout, only copied to `AssetGraphLayout::meta`.
+    pub meta: Option<String>,
+    /// Where edges attach to the nodes they join. Self-loops are drawn the
+    /// same either way.
+    pub edge_style: EdgeEndpointStyle,
+    /// Place nodes along their ranks with a single dagre alignment rather
+    /// than the balance of all four.
+    pub align: Option<Align>,
+    /// Fill in `AssetGraphLayout::edge_crossings`. Every pair of edges is
+    /// checked, which takes seconds on graphs with thousands of edges.
+    pub count_edge_crossings: bool,
+}
 
-            if !should_render(graph_data.nodes.get(downstream_id)) {
-                links_to_assets_outside_graphed_set.insert(downstream_id.clone(), true);
-            } else if !should_render(graph_data.nodes.get(upstream_id)) {
-                links_to_assets_outside_graphed_set.insert(upstream_id.clone(), true);
-            }
+impl Default for LayoutAssetGraphOptions {
+    fn default() -> Self {
+        Self {
+            horizontalDAGs: false,
+            rankdir: None,
+            nodesep: 50.0,
+            ranksep: 50.0,
+            edgesep: 20.0,
+            margin: 100,
+            group_padding: IPoint { x: 15.0, y: 70.0 },
+            node_dimensions_fn: None,
+            collapsed_groups: HashSet::new(),
+            self_loops: true,
+            pack_components: false,
+            link_name_max_length: ASSET_LINK_NAME_MAX_LENGTH,
+            node_name_max_length: ASSET_NODE_NAME_MAX_LENGTH,
+            link_height: 90.0,
+            order_hint: HashMap::new(),
+            arrow_gap: 5.0,
+            group_order: vec![],
+            acyclicer: Acyclicer::default(),
+            rank_overrides: HashMap::new(),
+            max_nodes: None,
+            edge_insets: None,
+            hide_sources: false,
+            snap_grid: None,
+            route_around_groups: false,
+            compact: false,
+            meta: None,
+            edge_style: EdgeEndpointStyle::default(),
+            align: None,
+            count_edge_crossings: false,
+        }
+    }
+}
+
+impl LayoutAssetGraphOptions {
+    pub fn builder() -> OptionsBuilder {
+        OptionsBuilder::default()
+    }
+
+    pub fn rankdir(&self) -> RankDir {
+        self.rankdir.unwrap_or(if self.horizontalDAGs {
+            RankDir::LR
+        } else {
+            RankDir::TB
+        })
+    }
+}
+
+/// Chainable construction of `LayoutAssetGraphOptions`. Anything not set
+/// keeps its default.
+#[derive(Debug, Clone, Default)]
+pub struct OptionsBuilder {
+    opts: LayoutAssetGraphOptions,
+}
+
+impl OptionsBuilder {
+    pub fn horizontal(mut self, horizontal: bool) -> Self {
+        self.opts.horizontalDAGs = horizontal;
+        self
+    }
+
+    pub fn rankdir(mut self, rankdir: RankDir) -> Self {
+        self.opts.rankdir = Some(rankdir);
+        self
+    }
+
+    pub fn nodesep(mut self, nodesep: f32) -> Self {
+        self.opts.nodesep = nodesep;
+        self
+    }
+
+    pub fn ranksep(mut self, ranksep: f32) -> Self {
+        self.opts.ranksep = ranksep;
+        self
+    }
+
+    pub fn edgesep(mut self, edgesep: f32) -> Self {
+        self.opts.edgesep = edgesep;
+        self
+    }
+
+    pub fn margin(mut self, margin: i32) -> Self {
+        self.opts.margin = margin;
+        self
+    }
+
+    pub fn group_padding(mut self, group_padding: IPoint) -> Self {
+        self.opts.group_padding = group_padding;
+        self
+    }
+
+    pub fn node_dimensions(
+        mut self,
+        dimensions_fn: impl Fn(&AssetNode) -> IBounds + 'static,
+    ) -> Self {
+        self.opts.node_dimensions_fn = Some(NodeDimensionsFn(Rc::new(dimensions_fn)));
+        self
+    }
+
+    pub fn collapsed_groups(mut self, collapsed_groups: HashSet<String>) -> Self {
+        self.opts.collapsed_groups = collapsed_groups;
+        self
+    }
+
+    pub fn self_loops(mut self, self_loops: bool) -> Self {
+        self.opts.self_loops = self_loops;
+        self
+    }
+
+    pub fn pack_components(mut self, pack_components: bool) -> Self {
+        self.opts.pack_components = pack_components;
+        self
+    }
+
+    pub fn link_name_max_length(mut self, link_name_max_length: usize) -> Self {
+        self.opts.link_name_max_length = link_name_max_length;
+        self
+    }
+
+    pub fn node_name_max_length(mut self, node_name_max_length: usize) -> Self {
+        self.opts.node_name_max_length = node_name_max_length;
+        self
+    }
+
+    pub fn link_height(mut self, link_height: f32) -> Self {
+        self.opts.link_height = link_height;
+        self
+    }
+
+    pub fn order_hint(mut self, order_hint: HashMap<GraphId, f32>) -> Self {
+        self.opts.order_hint = order_hint;
+        self
+    }
+
+    pub fn arrow_gap(mut self, arrow_gap: f32) -> Self {
+        self.opts.arrow_gap = arrow_gap;
+        self
+    }
+
+    pub fn group_order(mut self, group_order: Vec<String>) -> Self {
+        self.opts.group_order = group_order;
+        self
+    }
+
+    pub fn acyclicer(mut self, acyclicer: Acyclicer) -> Self {
+        self.opts.acyclicer = acyclicer;
+        self
+    }
+
+    pub fn rank_overrides(mut self, rank_overrides: HashMap<GraphId, i32>) -> Self {
+        self.opts.rank_overrides = rank_overrides;
+        self
+    }
+
+    pub fn max_nodes(mut self, max_nodes: usize) -> Self {
+        self.opts.max_nodes = Some(max_nodes);
+        self
+    }
+
+    pub fn edge_insets(mut self, edge_insets: EdgeInsets) -> Self {
+        self.opts.edge_insets = Some(edge_insets);
+        self
+    }
+
+    pub fn hide_sources(mut self, hide_sources: bool) -> Self {
+        self.opts.hide_sources = hide_sources;
+        self
+    }
+
+    pub fn snap_grid(mut self, snap_grid: f32) -> Self {
+        self.opts.snap_grid = Some(snap_grid);
+        self
+    }
+
+    pub fn route_around_groups(mut self, route_around_groups: bool) -> Self {
+        self.opts.route_around_groups = route_around_groups;
+        self
+    }
+
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.opts.compact = compact;
+        self
+    }
+
+    pub fn meta(mut self, meta: String) -> Self {
+        self.opts.meta = Some(meta);
+        self
+    }
+
+    pub fn edge_style(mut self, edge_style: EdgeEndpointStyle) -> Self {
+        self.opts.edge_style = edge_style;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.opts.align = Some(align);
+        self
+    }
+
+    pub fn count_edge_crossings(mut self, count_edge_crossings: bool) -> Self {
+        self.opts.count_edge_crossings = count_edge_crossings;
+        self
+    }
+
+    pub fn build(self) -> LayoutAssetGraphOptions {
+        self.opts
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct LayoutAssetGraphArgs {
+    pub graphData: GraphData,
+    pub opts: LayoutAssetGraphOptions,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutError {
+    /// A node id that should be a JSON-encoded asset key path isn't one.
+    MalformedNodeId(String),
+    /// An id referenced during layout has no corresponding node.
+    MissingNode(String),
+    /// The asset's rank override can't be met without an edge pointing
+    /// backwards or spanning fewer ranks than it needs.
+    RankConflict(String),
+    /// The graph has more assets to render than `max_nodes` allows.
+    TooLarge { count: usize, limit: usize },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::MalformedNodeId(id) => write!(f, "malformed node id: {}", id),
+            LayoutError::MissingNode(id) => write!(f, "missing node: {}", id),
+            LayoutError::RankConflict(id) => write!(f, "conflicting rank override: {}", id),
+            LayoutError::TooLarge { count, limit } => {
+                write!(f, "graph too large: {} assets, limit is {}", count, limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+#[wasm_bindgen(js_name = layout_asset_graph)]
+pub fn layout_asset_graph_json(args: &str) -> String {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let args_json: Result<LayoutAssetGraphArgs, serde_json::Error> = serde_json::from_str(args);
+    let mut args = LayoutAssetGraphArgs::default();
+    match args_json {
+        Ok(resolved_args) => args = resolved_args,
+        Err(err) => {
+            // Handle the error, for example, print the error message.
+            eprintln!("Error: {}", err);
+        }
+    }
+
+    match layout_asset_graph(&args.graphData, &args.opts) {
+        Ok(layout) => serde_json::to_string(&layout).unwrap_throw(),
+        Err(err) => wasm_bindgen::throw_str(&err.to_string()),
+    }
+}
+
+/// Lays out a JSON-encoded `GraphData` and returns the layout as a JS object.
+/// Failures reject with the error message.
+#[wasm_bindgen]
+pub fn layout_asset_graph_js(graph_data_json: &str, horizontal: bool) -> Result<JsValue, JsValue> {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let layout_json = layout_graph_data_json(graph_data_json, horizontal)
+        .map_err(|err| JsValue::from_str(&err))?;
+    js_sys::JSON::parse(&layout_json)
+}
+
+// Everything `layout_asset_graph_js` does short of `JSON.parse`, so that it
+// can be tested without a JS engine.
+fn layout_graph_data_json(graph_data_json: &str, horizontal: bool) -> Result<String, String> {
+    let graph_data: GraphData =
+        serde_json::from_str(graph_data_json).map_err(|err| err.to_string())?;
+    let opts = LayoutAssetGraphOptions {
+        horizontalDAGs: horizontal,
+        ..Default::default()
+    };
+    let layout = layout_asset_graph(&graph_data, &opts).map_err(|err| err.to_string())?;
+    serde_json::to_string(&layout).map_err(|err| err.to_string())
+}
+
+/// Same arguments as `layout_asset_graph`, but the layout comes back as a JS
+/// object in React Flow's shape (see `AssetGraphLayout::to_react_flow`).
+#[wasm_bindgen]
+pub fn layout_asset_graph_react_flow(args: &str) -> Result<JsValue, JsValue> {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let args: LayoutAssetGraphArgs =
+        serde_json::from_str(args).map_err(|err| JsValue::from_str(&err.to_string()))?;
+    let layout = layout_asset_graph(&args.graphData, &args.opts)
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+    let react_flow_json = serde_json::to_string(&layout.to_react_flow())
+        .map_err(|err| JsValue::from_str(&err.to_string()))?;
+    js_sys::JSON::parse(&react_flow_json)
+}
+
+// Callers may describe each dependency from either side, so this takes edges
+// from both maps. `upstream` is keyed by the downstream asset. Pairs are
+// `(upstream, downstream)`, sorted.
+fn dependency_pairs(graph_data: &GraphData) -> Vec<(&GraphId, &GraphId)> {
+    let mut dependencies: Vec<(&GraphId, &GraphId)> = graph_data
+        .downstream
+        .iter()
+        .flat_map(|(upstream_id, downstream)| downstream.keys().map(move |id| (upstream_id, id)))
+        .chain(
+            graph_data
+                .upstream
+                .iter()
+                .flat_map(|(downstream_id, upstream)| {
+                    upstream.keys().map(move |id| (id, downstream_id))
+                }),
+        )
+        .collect();
+    dependencies.sort();
+    dependencies
+}
+
+pub fn layout_asset_graph(
+    graph_data: &GraphData,
+    opts: &LayoutAssetGraphOptions,
+) -> Result<AssetGraphLayout, LayoutError> {
+    if opts.hide_sources {
+        let visible_opts = LayoutAssetGraphOptions {
+            hide_sources: false,
+            ..opts.clone()
+        };
+        return layout_asset_graph(&without_hidden_sources(graph_data), &visible_opts);
+    }
+    // Counts the assets that would be rendered, as `should_render` below.
+    if let Some(limit) = opts.max_nodes {
+        let count = graph_data
+            .nodes
+            .values()
+            .filter(|node| !node.definition.opNames.is_empty())
+            .count();
+        if count > limit {
+            return Err(LayoutError::TooLarge { count, limit });
+        }
+    }
+    if opts.pack_components {
+        return layout_packed_components(graph_data, opts);
+    }
+    layout_asset_graph_with(graph_data, opts, OrderedGraph::Build, &HashMap::new())
+}
+
+/// An asset graph that has been ranked and ordered once, so it can be laid out
+/// again with some assets pinned in place, while one is being dragged say,
+/// without repeating either step. They're where nearly all of a layout's time
+/// goes.
+///
+/// Only positions can change between layouts. The graph data and options are
+/// the ones given to `new`; make a new `RankedAssetGraph` once assets,
+/// dependencies, sizes or options change. `pack_components` is ignored, and the
+/// graph is laid out in one piece.
+pub struct RankedAssetGraph {
+    graph_data: GraphData,
+    opts: LayoutAssetGraphOptions,
+    ordered: Option<Graph<GraphConfig, GraphNode, GraphEdge>>,
+    layout: AssetGraphLayout,
+}
+
+impl RankedAssetGraph {
+    pub fn new(
+        graph_data: &GraphData,
+        opts: &LayoutAssetGraphOptions,
+    ) -> Result<Self, LayoutError> {
+        let (graph_data, opts) = if opts.hide_sources {
+            (
+                without_hidden_sources(graph_data),
+                LayoutAssetGraphOptions {
+                    hide_sources: false,
+                    pack_components: false,
+                    ..opts.clone()
+                },
+            )
+        } else {
+            (
+                graph_data.clone(),
+                LayoutAssetGraphOptions {
+                    pack_components: false,
+                    ..opts.clone()
+                },
+            )
+        };
+        let mut ordered = None;
+        let layout = layout_asset_graph_with(
+            &graph_data,
+            &opts,
+            OrderedGraph::BuildAndKeep(&mut ordered),
+            &HashMap::new(),
+        )?;
+        Ok(RankedAssetGraph {
+            graph_data,
+            opts,
+            ordered,
+            layout,
+        })
+    }
+
+    /// The layout with nothing pinned, as `layout_asset_graph` would give it.
+    pub fn layout(&self) -> &AssetGraphLayout {
+        &self.layout
+    }
+
+    /// Lays the graph out again with the assets (or collapsed groups) in
+    /// `fixed` centred on the given points, in the coordinates of `layout`.
+    /// dagre's coordinate assignment is re-run on the ranks and orders found by
+    /// `new`, then each pinned node is moved along its rank, and the nodes
+    /// beside it are pushed along just enough to keep their spacing. Edges are
+    /// routed afresh through the moved nodes.
+    ///
+    /// Nothing changes rank or order, so a point only sets the position along
+    /// the node's rank: x in vertical layouts, y in horizontal ones. The other
+    /// coordinate stays where the rank puts it. Pins on one rank that leave
+    /// too little room between them are kept anyway, and the nodes between
+    /// them overlap. `compact` and `snap_grid` still apply afterwards.
+    pub fn relayout_preserving_ranks(
+        &self,
+        fixed: &HashMap<GraphId, IPoint>,
+    ) -> Result<AssetGraphLayout, LayoutError> {
+        match &self.ordered {
+            Some(ordered) => layout_asset_graph_with(
+                &self.graph_data,
+                &self.opts,
+                OrderedGraph::Reuse(ordered),
+                fixed,
+            ),
+            // Nothing was rendered, so there's nothing to pin.
+            None => Ok(self.layout.clone()),
+        }
+    }
+}
+
+// Where `layout_asset_graph_with` gets its ranked and ordered dagre graph.
+enum OrderedGraph<'a> {
+    Build,
+    /// Build it, and keep a copy as it is before coordinate assignment.
+    BuildAndKeep(&'a mut Option<Graph<GraphConfig, GraphNode, GraphEdge>>),
+    /// Lay out a copy of one kept earlier for the same graph data and options.
+    Reuse(&'a Graph<GraphConfig, GraphNode, GraphEdge>),
+}
+
+// The layout itself, once the options that lay out something other than
+// `graph_data` as given have been dealt with. Nodes in `fixed` are pinned as in
+// `RankedAssetGraph::relayout_preserving_ranks`.
+fn layout_asset_graph_with(
+    graph_data: &GraphData,
+    opts: &LayoutAssetGraphOptions,
+    ordered_graph: OrderedGraph,
+    fixed: &HashMap<GraphId, IPoint>,
+) -> Result<AssetGraphLayout, LayoutError> {
+    let mut g: Graph<GraphConfig, GraphNode, GraphEdge> =
+        graphlib_rust::graph::Graph::new(Some(GraphOption {
+            compound: Some(true),
+            directed: Some(true),
+            multigraph: Some(false),
+        }));
+    let rankdir = opts.rankdir();
+    let config = g.graph_mut();
+    config.rankdir = Some(rankdir.to_dagre());
+    config.nodesep = Some(opts.nodesep);
+    config.ranksep = Some(opts.ranksep);
+    config.edgesep = Some(opts.edgesep);
+    config.align = opts.align.map(Align::to_dagre);
+
+    let mut nodes: HashMap<GraphId, AssetLayout> = HashMap::new();
+    let mut groups: HashMap<String, GroupLayout> = HashMap::new();
+    let mut links_to_assets_outside_graphed_set: HashMap<GraphId, bool> = HashMap::new();
+
+    let should_render = |node: Option<&AssetGraphNode>| -> bool {
+        if let Some(node) = node {
+            !node.definition.opNames.is_empty()
+        } else {
+            false
+        }
+    };
+
+    // Position of a group in `opts.group_order`, with unlisted groups last.
+    let group_position = |group_id: &String| {
+        opts.group_order
+            .iter()
+            .position(|id| id == group_id)
+            .unwrap_or(usize::MAX)
+    };
+
+    // dagre's result depends on insertion order, so everything that goes into
+    // `g` is sorted first to keep layouts reproducible.
+    let mut rendered_nodes: Vec<&AssetGraphNode> = graph_data
+        .nodes
+        .values()
+        .filter(|node| should_render(Some(node)))
+        .collect();
+    rendered_nodes.sort_by_cached_key(|node| {
+        let group_position = match node.definition.groupName {
+            Some(_) => group_position(&parent_node_id_for_node(node)),
+            None => usize::MAX,
+        };
+        (group_position, node.id.clone())
+    });
+
+    if rendered_nodes.is_empty() {
+        return Ok(AssetGraphLayout::default());
+    }
+
+    for node in &rendered_nodes {
+        if let Some(group_name) = &node.definition.groupName {
+            let id = parent_node_id_for_node(node);
+            groups.insert(
+                id.clone(),
+                GroupLayout {
+                    id: id.clone(),
+                    groupName: group_name.clone(),
+                    repositoryName: node.definition.repository.name.clone(),
+                    repositoryLocationName: node.definition.repository.location.name.clone(),
+                    repositoryDisambiguationRequired: false,
+                    bounds: IBounds {
+                        x: 0.0,
+                        y: 0.0,
+                        width: 0.0,
+                        height: 0.0,
+                    },
+                    header_bounds: IBounds::default(),
+                    member_ids: vec![],
+                },
+            );
+        }
+    }
+
+    // Members of a collapsed group are laid out as a single node that takes the
+    // group's id.
+    let collapsed_group_for_node: HashMap<&GraphId, String> = rendered_nodes
+        .iter()
+        .filter(|node| node.definition.groupName.is_some())
+        .map(|node| (&node.id, parent_node_id_for_node(node)))
+        .filter(|(_, group_id)| opts.collapsed_groups.contains(group_id))
+        .collect();
+    let layout_id = |id: &GraphId| -> GraphId {
+        collapsed_group_for_node
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| id.clone())
+    };
+
+    let show_groups = groups.len() > 1;
+    let mut group_ids: Vec<&String> = groups.keys().collect();
+    group_ids.sort_by_key(|id| (group_position(id), *id));
+    for group_id in group_ids {
+        if opts.collapsed_groups.contains(group_id) {
+            let members: Vec<&AssetNode> = rendered_nodes
+                .iter()
+                .filter(|node| collapsed_group_for_node.get(&node.id) == Some(group_id))
+                .map(|node| &node.definition)
+                .collect();
+            let collapsed_group_dimensions = get_collapsed_group_dimensions(opts, &members);
+            let g_node = GraphNode {
+                width: collapsed_group_dimensions.width,
+                height: collapsed_group_dimensions.height,
+                ..Default::default()
+            };
+            g.set_node(group_id.clone(), Some(g_node));
+        } else if show_groups {
+            g.set_node(group_id.clone(), Some(GraphNode::default()));
+        }
+    }
+
+    for node in &rendered_nodes {
+        if collapsed_group_for_node.contains_key(&node.id) {
+            continue;
+        }
+        let asset_node_dimensions = asset_node_dimensions(opts, &node.definition);
+        let g_node = GraphNode {
+            width: asset_node_dimensions.width,
+            height: asset_node_dimensions.height,
+            ..Default::default()
+        };
+        g.set_node(node.id.clone(), Some(g_node));
+
+        if show_groups && node.definition.groupName.is_some() {
+            g.set_parent(&node.id, Some(parent_node_id_for_node(node)))
+                .unwrap_throw();
+        }
+    }
+
+    for (upstream_id, downstream_id) in dependency_pairs(graph_data) {
+        if !should_render(graph_data.nodes.get(downstream_id))
+            && !should_render(graph_data.nodes.get(upstream_id))
+        {
+            continue;
+        }
+        let (v, w) = (layout_id(upstream_id), layout_id(downstream_id));
+        if v == w && (!opts.self_loops || collapsed_group_for_node.contains_key(upstream_id)) {
+            continue;
+        }
+        if g.has_edge(&v, &w, None) {
+            continue;
+        }
+
+        let hints = graph_data
+            .edgeHints
+            .get(upstream_id)
+            .and_then(|hints| hints.get(downstream_id));
+        let label = graph_data
+            .edgeLabels
+            .get(upstream_id)
+            .and_then(|labels| labels.get(downstream_id));
+        let edge_label = (hints.is_some() || label.is_some()).then(|| GraphEdge {
+            minlen: hints.map(|hints| hints.minlen as f32),
+            weight: hints.map(|hints| hints.weight as f32),
+            width: label.map(|label| label.width),
+            height: label.map(|label| label.height),
+            labelpos: label.map(|_| "c".to_string()),
+            ..Default::default()
+        });
+        g.set_edge(&v, &w, edge_label, None).unwrap_throw();
+
+        if !should_render(graph_data.nodes.get(downstream_id)) {
+            links_to_assets_outside_graphed_set.insert(downstream_id.clone(), true);
+        } else if !should_render(graph_data.nodes.get(upstream_id)) {
+            links_to_assets_outside_graphed_set.insert(upstream_id.clone(), true);
         }
     }
 
     for id in links_to_assets_outside_graphed_set.keys() {
-        let path: Vec<String> = serde_json::from_str(id).unwrap();
+        let path: Vec<String> =
+            serde_json::from_str(id).map_err(|_| LayoutError::MalformedNodeId(id.clone()))?;
         let label = path.last().unwrap_or(&"".to_string()).clone();
-        let asset_link_dimensions = get_asset_link_dimensions(&label, &opts);
+        let asset_link_dimensions = get_asset_link_dimensions(&label, opts);
+        if let Some(g_node) = g.node_mut(id) {
+            g_node.width = asset_link_dimensions.width;
+            g_node.height = asset_link_dimensions.height;
+        }
         nodes.insert(
             id.clone(),
             AssetLayout {
                 id: id.clone(),
+                anchors: NodeAnchors::from_bounds(&asset_link_dimensions),
                 bounds: asset_link_dimensions,
+                rank: None,
+                kind: Some("link".to_string()),
+                group: None,
             },
         );
     }
 
-    log!("Starting layouting!");
-    layout::layout(&mut g);
-    log!("Finished layouting!");
+    // Reverse a feedback arc set so dagre receives an acyclic graph. The
+    // reversed edges are restored to their original direction when emitted.
+    // dagre_rust's own cycle breaking can't be used: its "greedy" is a stub,
+    // and the named edges its DFS reversal adds break `normalize`.
+    let mut reversed_edges: Vec<(GraphId, GraphId)> = Vec::new();
+    let mut reversed_onto_existing: Vec<(GraphId, GraphId)> = Vec::new();
+    if !algo::find_cycles(&g).is_empty() {
+        let fas = match opts.acyclicer {
+            Acyclicer::Greedy => algo::greedy_fas(&g, |_| 1),
+            Acyclicer::Dfs => algo::dfs_fas(&g),
+        };
+        for edge in fas {
+            if edge.v == edge.w {
+                continue;
+            }
+            let edge_label = g.edge(&edge.v, &edge.w, None).cloned();
+            g.remove_edge(&edge.v, &edge.w, None);
+            if let Some(existing) = g.edge_mut(&edge.w, &edge.v, None) {
+                // Both edges now route along the one dagre edge, so it
+                // carries both edges' hints and room for the larger label.
+                if let Some(removed) = &edge_label {
+                    merge_edge_hints(existing, removed);
+                }
+                reversed_onto_existing.push((edge.w.clone(), edge.v.clone()));
+            } else {
+                g.set_edge(&edge.w, &edge.v, edge_label, None)
+                    .unwrap_throw();
+            }
+            reversed_edges.push((edge.v, edge.w));
+        }
+    }
+
+    log!("Starting layouting!");
+    // Equivalent to `layout::layout`, but keeps the layout graph around since
+    // ranks aren't copied back to `g`.
+    let mut layout_graph = match ordered_graph {
+        OrderedGraph::Reuse(ordered) => ordered.clone_graph(),
+        OrderedGraph::Build | OrderedGraph::BuildAndKeep(_) => {
+            let mut layout_graph = layout::build_layout_graph(&g);
+            dagre_layout::rank_and_order(&mut layout_graph, &opts.rank_overrides)?;
+            if let OrderedGraph::BuildAndKeep(kept) = ordered_graph {
+                *kept = Some(layout_graph.clone_graph());
+            }
+            layout_graph
+        }
+    };
+    let pins: HashMap<String, f32> = fixed
+        .iter()
+        .map(|(id, point)| {
+            let along = if rankdir.is_horizontal() {
+                point.y
+            } else {
+                point.x
+            };
+            (id.clone(), along)
+        })
+        .collect();
+    dagre_layout::place(&mut layout_graph, &pins);
+    layout::update_input_graph(&mut g, &layout_graph);
+    log!("Finished layouting!");
+
+    // dagre leaves gaps between ranks for edge labels and group borders, so
+    // number the ranks that hold assets consecutively.
+    let rank_for_node = |id: &GraphId| -> Option<i32> {
+        if id.starts_with(GROUP_NODE_PREFIX) || links_to_assets_outside_graphed_set.contains_key(id)
+        {
+            return None;
+        }
+        layout_graph.node(id).and_then(|node| node.rank)
+    };
+    let mut asset_ranks: Vec<i32> = g.nodes().iter().filter_map(rank_for_node).collect();
+    asset_ranks.sort();
+    asset_ranks.dedup();
+
+    if !opts.order_hint.is_empty() {
+        let mut hinted_by_rank: BTreeMap<i32, Vec<GraphId>> = BTreeMap::new();
+        for id in g.nodes() {
+            if let (Some(rank), true) = (rank_for_node(&id), opts.order_hint.contains_key(&id)) {
+                hinted_by_rank.entry(rank).or_default().push(id);
+            }
+        }
+
+        let mut fixed: HashMap<GraphId, IPoint> = HashMap::new();
+        for mut ids in hinted_by_rank.into_values() {
+            let center = |id: &GraphId| {
+                let node = g.node(id).unwrap_throw();
+                IPoint {
+                    x: node.x,
+                    y: node.y,
+                }
+            };
+            // Positions along the rank, so x for vertical layouts and y for
+            // horizontal ones.
+            let along = |p: &IPoint| if rankdir.is_horizontal() { p.y } else { p.x };
+            let mut slots: Vec<IPoint> = ids.iter().map(center).collect();
+            slots.sort_by(|a, b| along(a).total_cmp(&along(b)));
+            ids.sort_by(|a, b| {
+                opts.order_hint[a]
+                    .total_cmp(&opts.order_hint[b])
+                    .then(a.cmp(b))
+            });
+            for (id, slot) in ids.into_iter().zip(slots) {
+                fixed.insert(id, slot);
+            }
+        }
+        move_nodes(&mut g, &fixed);
+    }
+
+    if opts.compact {
+        // Expanded groups are drawn around their members later, so only
+        // assets, link stubs and collapsed groups hold a rank open, along with
+        // the padding that will go around each group's members.
+        let holds_rank =
+            |id: &GraphId| !id.starts_with(GROUP_NODE_PREFIX) || opts.collapsed_groups.contains(id);
+        let group_padding = if rankdir.is_horizontal() {
+            (opts.group_padding.y, opts.group_padding.x)
+        } else {
+            (opts.group_padding.y, opts.group_padding.y)
+        };
+        compact_ranks(
+            &mut g,
+            rankdir.is_horizontal(),
+            holds_rank,
+            group_padding,
+            opts.ranksep,
+        );
+    }
+
+    if let Some(grid) = opts.snap_grid.filter(|grid| *grid > 0.0) {
+        snap_to_grid(&mut g, grid);
+    }
+
+    // dagre records the extent of everything it placed, edge labels included,
+    // in the graph label. Order hints and snapping move nodes afterwards, so
+    // the scan below still widens it if a node ends up further out.
+    let mut max_width = g.graph().width.round() as i32;
+    let mut max_height = g.graph().height.round() as i32;
+
+    for id in g.nodes() {
+        if let Some(dagre_node) = g.node(&id) {
+            let bounds = dagre_node_bounds(dagre_node);
+            if !id.starts_with(GROUP_NODE_PREFIX) {
+                let rank = rank_for_node(&id)
+                    .and_then(|rank| asset_ranks.binary_search(&rank).ok())
+                    .map(|layer| layer as i32);
+                let is_link = links_to_assets_outside_graphed_set.contains_key(&id);
+                let kind = asset_layout_kind(graph_data.nodes.get(&id), is_link);
+                let group = graph_data
+                    .nodes
+                    .get(&id)
+                    .filter(|_| !is_link)
+                    .and_then(group_for_node);
+                let id_copy = id.clone();
+                nodes.insert(
+                    id,
+                    AssetLayout {
+                        id: id_copy,
+                        anchors: NodeAnchors::from_bounds(&bounds),
+                        bounds: bounds.clone(),
+                        rank,
+                        kind: Some(kind),
+                        group,
+                    },
+                );
+            }
+            max_width = max_width.max((dagre_node.x + dagre_node.width / 2.0).round() as i32);
+            max_height = max_height.max((dagre_node.y + dagre_node.height / 2.0).round() as i32);
+        }
+    }
+
+    for node in &rendered_nodes {
+        if node.definition.groupName.is_some() {
+            let group_id = parent_node_id_for_node(node);
+            let group_for_id = groups
+                .get_mut(&group_id)
+                .ok_or_else(|| LayoutError::MissingNode(group_id.clone()))?;
+            group_for_id.member_ids.push(node.id.clone());
+            if !show_groups || collapsed_group_for_node.contains_key(&node.id) {
+                continue;
+            }
+            let node_bounds = &nodes
+                .get(&node.id)
+                .ok_or_else(|| LayoutError::MissingNode(node.id.clone()))?
+                .bounds;
+            if group_for_id.bounds.width == 0.0 {
+                group_for_id.bounds = node_bounds.clone();
+            } else {
+                group_for_id.bounds = extend_bounds(&group_for_id.bounds, node_bounds);
+            }
+        }
+    }
+    if show_groups {
+        for group in groups.values_mut() {
+            if !opts.collapsed_groups.contains(&group.id) {
+                group.bounds = pad_group_bounds(&group.bounds, &opts.group_padding, rankdir);
+            }
+        }
+    }
+    for group in groups
+        .values_mut()
+        .filter(|group| opts.collapsed_groups.contains(&group.id))
+    {
+        if let Some(dagre_node) = g.node(&group.id) {
+            group.bounds = dagre_node_bounds(dagre_node);
+        }
+    }
+    for group in groups.values_mut() {
+        group.header_bounds =
+            group_header_bounds(&group.bounds, opts.group_padding.y, rankdir.is_horizontal());
+        group.member_ids.sort();
+    }
+
+    let mut edges: Vec<AssetLayoutEdge> = Vec::new();
+    let insets = opts
+        .edge_insets
+        .clone()
+        .unwrap_or_else(|| EdgeInsets::default_for(rankdir));
+
+    let mut logical_edges: Vec<(GraphId, GraphId)> = g
+        .edges()
+        .into_iter()
+        .map(|edge| (edge.v, edge.w))
+        .filter(|(v, w)| {
+            let stands_in_for_reversed = reversed_edges.contains(&(w.clone(), v.clone()));
+            !stands_in_for_reversed || reversed_onto_existing.contains(&(v.clone(), w.clone()))
+        })
+        .collect();
+    logical_edges.extend(reversed_edges);
+
+    for (v, w) in logical_edges {
+        let v_node = g
+            .node(&v)
+            .ok_or_else(|| LayoutError::MissingNode(v.clone()))?;
+        let w_node = g
+            .node(&w)
+            .ok_or_else(|| LayoutError::MissingNode(w.clone()))?;
+
+        if v == w {
+            edges.push(self_loop_edge(&v, v_node));
+            continue;
+        }
+
+        let x_inset = |id: &GraphId| {
+            if links_to_assets_outside_graphed_set.contains_key(id) {
+                16.0
+            } else {
+                insets.left
+            }
+        };
+        let (v_x_inset, w_x_inset) = (x_inset(&v), x_inset(&w));
+
+        // Inset edges leave and enter cards through the anchors on their
+        // sides facing along the flow, moved in by the insets. In vertical
+        // layouts they attach near the left of the card rather than at its
+        // middle.
+        let (v_anchors, w_anchors) = (
+            NodeAnchors::from_bounds(&dagre_node_bounds(v_node)),
+            NodeAnchors::from_bounds(&dagre_node_bounds(w_node)),
+        );
+        let (from, to) = match (opts.edge_style, rankdir) {
+            (EdgeEndpointStyle::Center, _) => (
+                IPoint {
+                    x: v_node.x,
+                    y: v_node.y,
+                },
+                IPoint {
+                    x: w_node.x,
+                    y: w_node.y,
+                },
+            ),
+            (EdgeEndpointStyle::Inset, RankDir::LR) => (
+                IPoint {
+                    x: v_anchors.right.x - insets.right,
+                    y: v_anchors.right.y,
+                },
+                IPoint {
+                    x: w_anchors.left.x + insets.left - opts.arrow_gap,
+                    y: w_anchors.left.y,
+                },
+            ),
+            (EdgeEndpointStyle::Inset, RankDir::RL) => (
+                IPoint {
+                    x: v_anchors.left.x + insets.left,
+                    y: v_anchors.left.y,
+                },
+                IPoint {
+                    x: w_anchors.right.x - insets.right + opts.arrow_gap,
+                    y: w_anchors.right.y,
+                },
+            ),
+            (EdgeEndpointStyle::Inset, RankDir::TB) => (
+                IPoint {
+                    x: v_anchors.left.x + v_x_inset,
+                    y: v_anchors.bottom.y - insets.bottom,
+                },
+                IPoint {
+                    x: w_anchors.left.x + w_x_inset,
+                    y: w_anchors.top.y + insets.top,
+                },
+            ),
+            (EdgeEndpointStyle::Inset, RankDir::BT) => (
+                IPoint {
+                    x: v_anchors.left.x + v_x_inset,
+                    y: v_anchors.top.y + insets.top,
+                },
+                IPoint {
+                    x: w_anchors.left.x + w_x_inset,
+                    y: w_anchors.bottom.y - insets.bottom,
+                },
+            ),
+        };
+        let mut asset_layout_edge = AssetLayoutEdge {
+            from,
+            fromId: v.clone(),
+            to,
+            toId: w.clone(),
+            ..Default::default()
+        };
+
+        // Reversed edges were laid out from `w` to `v`, so walk their bend
+        // points backwards.
+        let (dagre_edge, reversed) = match g.edge(&v, &w, None) {
+            Some(edge) => (Some(edge), false),
+            None => (g.edge(&w, &v, None), true),
+        };
+        let mut bends: Vec<IPoint> = dagre_edge.map(interior_points).unwrap_or_default();
+        if reversed {
+            bends.reverse();
+        }
+        asset_layout_edge.label = dagre_edge
+            .filter(|edge| edge.width.unwrap_or(0.0) > 0.0)
+            .map(|edge| IPoint {
+                x: edge.x,
+                y: edge.y,
+            });
+        asset_layout_edge.reversed = reversed;
+        asset_layout_edge.points = std::iter::once(asset_layout_edge.from.clone())
+            .chain(bends)
+            .chain(std::iter::once(asset_layout_edge.to.clone()))
+            .collect();
+
+        edges.push(asset_layout_edge);
+    }
+
+    // Link stubs have no public rank, but dagre still placed them in one.
+    let mut ordered_node_ids: Vec<GraphId> = nodes.keys().cloned().collect();
+    let order_key = |id: &GraphId| {
+        let rank = layout_graph.node(id).and_then(|node| node.rank);
+        let bounds = &nodes[id].bounds;
+        let along = if rankdir.is_horizontal() {
+            bounds.y
+        } else {
+            bounds.x
+        };
+        (rank, along)
+    };
+    ordered_node_ids.sort_by(|a, b| {
+        let ((a_rank, a_along), (b_rank, b_along)) = (order_key(a), order_key(b));
+        a_rank
+            .cmp(&b_rank)
+            .then(a_along.total_cmp(&b_along))
+            .then(a.cmp(b))
+    });
+
+    let mut external_links: Vec<GraphId> =
+        links_to_assets_outside_graphed_set.into_keys().collect();
+    external_links.sort();
+
+    if opts.route_around_groups && show_groups {
+        for edge in edges.iter_mut().filter(|edge| edge.fromId != edge.toId) {
+            let endpoint_groups = [&edge.fromId, &edge.toId].map(|id| {
+                nodes
+                    .get(id)
+                    .and_then(|node| node.group.clone())
+                    .unwrap_or_else(|| id.clone())
+            });
+            let obstacles: Vec<&IBounds> = groups
+                .values()
+                .filter(|group| group.bounds.width > 0.0 && !endpoint_groups.contains(&group.id))
+                .map(|group| &group.bounds)
+                .collect();
+            edge.points = route_around(&edge.points, &obstacles);
+        }
+    }
+
+    if let Some(grid) = opts.snap_grid.filter(|grid| *grid > 0.0) {
+        for edge in edges.iter_mut() {
+            if let (Some(from_node), Some(to_node)) = (g.node(&edge.fromId), g.node(&edge.toId)) {
+                snap_edge_to_grid(edge, grid, from_node, to_node);
+            }
+        }
+    }
+
+    sort_edges(&mut edges);
+    let edge_crossings = opts
+        .count_edge_crossings
+        .then(|| count_edge_crossings(&edges));
+
+    Ok(AssetGraphLayout {
+        width: max_width + opts.margin,
+        height: max_height + opts.margin,
+        edge_crossings,
+        edges,
+        nodes,
+        ordered_node_ids,
+        groups,
+        external_links,
+        meta: opts.meta.clone(),
+    })
+}
+
+// `graph_data` minus the non-observable sources and every dependency, hint and
+// label that mentions one, so they don't come back as link stubs.
+fn without_hidden_sources(graph_data: &GraphData) -> GraphData {
+    let hidden: HashSet<&GraphId> = graph_data
+        .nodes
+        .values()
+        .filter(|node| node.definition.isSource && !node.definition.isObservable)
+        .map(|node| &node.id)
+        .collect();
+    fn visible<V: Clone>(
+        map: &HashMap<GraphId, HashMap<GraphId, V>>,
+        hidden: &HashSet<&GraphId>,
+    ) -> HashMap<GraphId, HashMap<GraphId, V>> {
+        map.iter()
+            .filter(|(id, _)| !hidden.contains(id))
+            .map(|(id, inner)| {
+                let inner = inner
+                    .iter()
+                    .filter(|(id, _)| !hidden.contains(id))
+                    .map(|(id, value)| (id.clone(), value.clone()))
+                    .collect();
+                (id.clone(), inner)
+            })
+            .collect()
+    }
+    GraphData {
+        nodes: graph_data
+            .nodes
+            .iter()
+            .filter(|(id, _)| !hidden.contains(id))
+            .map(|(id, node)| (id.clone(), node.clone()))
+            .collect(),
+        downstream: visible(&graph_data.downstream, &hidden),
+        upstream: visible(&graph_data.upstream, &hidden),
+        edgeHints: visible(&graph_data.edgeHints, &hidden),
+        edgeLabels: visible(&graph_data.edgeLabels, &hidden),
+    }
+}
+
+// Lays out each connected component on its own, then packs the results into
+// rows of roughly equal width, tallest first.
+fn layout_packed_components(
+    graph_data: &GraphData,
+    opts: &LayoutAssetGraphOptions,
+) -> Result<AssetGraphLayout, LayoutError> {
+    let mut connectivity: Graph<(), (), ()> = Graph::new(Some(GraphOption {
+        compound: Some(false),
+        directed: Some(false),
+        multigraph: Some(false),
+    }));
+    let mut ids: Vec<&GraphId> = graph_data.nodes.keys().collect();
+    ids.sort();
+    for id in ids {
+        connectivity.set_node(id.clone(), None);
+    }
+    for (upstream_id, downstream_id) in dependency_pairs(graph_data) {
+        connectivity
+            .set_edge(upstream_id, downstream_id, None, None)
+            .unwrap_throw();
+    }
+
+    let component_opts = LayoutAssetGraphOptions {
+        pack_components: false,
+        ..opts.clone()
+    };
+    let mut layouts: Vec<AssetGraphLayout> = vec![];
+    for component in algo::components(&connectivity) {
+        let members: HashSet<&GraphId> = component.iter().collect();
+        let component_data = GraphData {
+            nodes: entries_for(&graph_data.nodes, &members),
+            downstream: entries_for(&graph_data.downstream, &members),
+            upstream: entries_for(&graph_data.upstream, &members),
+            edgeHints: entries_for(&graph_data.edgeHints, &members),
+            edgeLabels: entries_for(&graph_data.edgeLabels, &members),
+        };
+        let layout = layout_asset_graph(&component_data, &component_opts)?;
+        if !layout.nodes.is_empty() {
+            layouts.push(layout);
+        }
+    }
+    layouts.sort_by_key(|layout| std::cmp::Reverse(layout.height));
+
+    // Each component's size already includes the margin, which doubles as the
+    // gap between neighbours.
+    let area: f64 = layouts
+        .iter()
+        .map(|layout| layout.width as f64 * layout.height as f64)
+        .sum();
+    let widest = layouts.iter().map(|layout| layout.width).max().unwrap_or(0);
+    let row_width = (area.sqrt() as i32).max(widest);
+
+    let mut packed = AssetGraphLayout {
+        meta: opts.meta.clone(),
+        ..Default::default()
+    };
+    let (mut x, mut y, mut row_height) = (0, 0, 0);
+    for layout in layouts {
+        if x > 0 && x + layout.width > row_width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        // Rounding each component's footprint up to the grid keeps every
+        // offset, and so every snapped coordinate, on it.
+        let (width, height) = match opts.snap_grid.filter(|grid| *grid > 0.0) {
+            Some(grid) => {
+                let round_up = |size: i32| ((size as f32 / grid).ceil() * grid) as i32;
+                (round_up(layout.width), round_up(layout.height))
+            }
+            None => (layout.width, layout.height),
+        };
+        merge_translated_layout(&mut packed, layout, x as f32, y as f32);
+        x += width;
+        row_height = row_height.max(height);
+        packed.width = packed.width.max(x);
+        packed.height = packed.height.max(y + row_height);
+    }
+    sort_edges(&mut packed.edges);
+    packed.external_links.sort();
+    packed.external_links.dedup();
+
+    Ok(packed)
+}
+
+fn entries_for<V: Clone>(
+    map: &HashMap<GraphId, V>,
+    members: &HashSet<&GraphId>,
+) -> HashMap<GraphId, V> {
+    map.iter()
+        .filter(|(id, _)| members.contains(id))
+        .map(|(id, value)| (id.clone(), value.clone()))
+        .collect()
+}
+
+fn merge_translated_layout(
+    into: &mut AssetGraphLayout,
+    layout: AssetGraphLayout,
+    dx: f32,
+    dy: f32,
+) {
+    let translate_point = |p: &IPoint| IPoint {
+        x: p.x + dx,
+        y: p.y + dy,
+    };
+    let translate_bounds = |b: &IBounds| IBounds {
+        x: b.x + dx,
+        y: b.y + dy,
+        ..b.clone()
+    };
+
+    // Components don't overlap, so their edges can't cross each other.
+    into.edge_crossings = match (into.edge_crossings, layout.edge_crossings) {
+        (Some(into), Some(layout)) => Some(into + layout),
+        (into, layout) => into.or(layout),
+    };
+    into.ordered_node_ids.extend(layout.ordered_node_ids);
+    into.external_links.extend(layout.external_links);
+    for (id, node) in layout.nodes {
+        let bounds = translate_bounds(&node.bounds);
+        let anchors = NodeAnchors::from_bounds(&bounds);
+        into.nodes.insert(
+            id,
+            AssetLayout {
+                bounds,
+                anchors,
+                ..node
+            },
+        );
+    }
+    for edge in layout.edges {
+        into.edges.push(AssetLayoutEdge {
+            from: translate_point(&edge.from),
+            to: translate_point(&edge.to),
+            points: edge.points.iter().map(translate_point).collect(),
+            label: edge.label.as_ref().map(translate_point),
+            ..edge
+        });
+    }
+    // A group can span several components. Groups that weren't drawn in a
+    // component have empty bounds and don't contribute.
+    for (id, group) in layout.groups {
+        let bounds = if group.bounds.width == 0.0 {
+            group.bounds.clone()
+        } else {
+            translate_bounds(&group.bounds)
+        };
+        let header_on_left = header_on_left(&group);
+        let header_size = if header_on_left {
+            group.header_bounds.width
+        } else {
+            group.header_bounds.height
+        };
+        if let Some(existing) = into.groups.get_mut(&id) {
+            existing.member_ids.extend(group.member_ids.iter().cloned());
+            existing.member_ids.sort();
+        }
+        match into.groups.get_mut(&id) {
+            Some(existing) if existing.bounds.width == 0.0 => existing.bounds = bounds,
+            Some(existing) if bounds.width != 0.0 => {
+                existing.bounds = extend_bounds(&existing.bounds, &bounds)
+            }
+            Some(_) => continue,
+            None => {
+                into.groups
+                    .insert(id.clone(), GroupLayout { bounds, ..group });
+            }
+        }
+        if let Some(existing) = into.groups.get_mut(&id) {
+            existing.header_bounds =
+                group_header_bounds(&existing.bounds, header_size, header_on_left);
+        }
+    }
+}
+
+// Shrinks every gap along the rank axis wider than `max_gap`, between the
+// bands taken up by nodes that `holds_rank`, by edge labels and by
+// `group_padding` before and after the members of each group, down to
+// `max_gap`. Coordinates past a gap move back by what it lost and those
+// inside it are scaled down, so edges keep their shape and nothing changes
+// order.
+fn compact_ranks(
+    g: &mut Graph<GraphConfig, GraphNode, GraphEdge>,
+    horizontal: bool,
+    holds_rank: impl Fn(&GraphId) -> bool,
+    group_padding: (f32, f32),
+    max_gap: f32,
+) {
+    let band = |v: &GraphId| {
+        let node = g.node(v).unwrap();
+        let (center, size) = if horizontal {
+            (node.x, node.width)
+        } else {
+            (node.y, node.height)
+        };
+        (center - size / 2.0, center + size / 2.0)
+    };
+    let mut bands: Vec<(f32, f32)> = vec![];
+    for v in g.nodes() {
+        if holds_rank(&v) {
+            bands.push(band(&v));
+            continue;
+        }
+        let members: Vec<(f32, f32)> = g.children(&v).iter().map(band).collect();
+        if let (Some(start), Some(end)) = (
+            members.iter().map(|band| band.0).min_by(f32::total_cmp),
+            members.iter().map(|band| band.1).max_by(f32::total_cmp),
+        ) {
+            bands.push((start - group_padding.0, start));
+            bands.push((end, end + group_padding.1));
+        }
+    }
+    for e in g.edges() {
+        let edge = g.edge_with_obj(&e).unwrap();
+        let (width, height) = (edge.width.unwrap_or(0.0), edge.height.unwrap_or(0.0));
+        if width > 0.0 && height > 0.0 {
+            let (center, size) = if horizontal {
+                (edge.x, width)
+            } else {
+                (edge.y, height)
+            };
+            bands.push((center - size / 2.0, center + size / 2.0));
+        }
+    }
+    bands.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // (start, end, how much it shrinks) for each gap that's too wide.
+    let mut gaps: Vec<(f32, f32, f32)> = vec![];
+    let mut bands = bands.into_iter();
+    let Some((_, mut end)) = bands.next() else {
+        return;
+    };
+    for (start, band_end) in bands {
+        if start - end > max_gap {
+            gaps.push((end, start, start - end - max_gap));
+        }
+        end = end.max(band_end);
+    }
+    if gaps.is_empty() {
+        return;
+    }
+
+    let compact = |value: f32| {
+        let mut shift = 0.0;
+        for &(start, end, removed) in &gaps {
+            if value >= end {
+                shift += removed;
+            } else {
+                if value > start {
+                    shift += removed * (value - start) / (end - start);
+                }
+                break;
+            }
+        }
+        value - shift
+    };
+    // Expanded groups' own boxes are mapped edge by edge, since they can
+    // span a gap.
+    let compact_span = |center: f32, size: f32| {
+        let (start, end) = (compact(center - size / 2.0), compact(center + size / 2.0));
+        ((start + end) / 2.0, end - start)
+    };
+    for v in g.nodes() {
+        let node = g.node_mut(&v).unwrap();
+        if horizontal {
+            (node.x, node.width) = compact_span(node.x, node.width);
+        } else {
+            (node.y, node.height) = compact_span(node.y, node.height);
+        }
+    }
+    for e in g.edges() {
+        let edge = g.edge_mut_with_obj(&e).unwrap();
+        for point in edge.points.iter_mut().flatten() {
+            if horizontal {
+                point.x = compact(point.x);
+            } else {
+                point.y = compact(point.y);
+            }
+        }
+        if horizontal {
+            edge.x = compact(edge.x);
+        } else {
+            edge.y = compact(edge.y);
+        }
+    }
+    let mut graph_label = g.graph().clone();
+    if horizontal {
+        graph_label.width -= gaps.iter().map(|gap| gap.2).sum::<f32>();
+    } else {
+        graph_label.height -= gaps.iter().map(|gap| gap.2).sum::<f32>();
+    }
+    g.set_graph(graph_label);
+}
+
+// Moves every node so its top-left corner lands on the grid, and puts edge
+// bend points and labels on the nearest grid point. Node sizes don't change,
+// so edge endpoints worked out from the nodes afterwards still meet them.
+fn snap_to_grid(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>, grid: f32) {
+    let snap = |value: f32| (value / grid).round() * grid;
+    for v in g.nodes() {
+        let node = g.node_mut(&v).unwrap();
+        node.x = snap(node.x - node.width / 2.0) + node.width / 2.0;
+        node.y = snap(node.y - node.height / 2.0) + node.height / 2.0;
+    }
+    for e in g.edges() {
+        let edge = g.edge_mut_with_obj(&e).unwrap();
+        for point in edge.points.iter_mut().flatten() {
+            point.x = snap(point.x);
+            point.y = snap(point.y);
+        }
+        edge.x = snap(edge.x);
+        edge.y = snap(edge.y);
+    }
+}
+
+// Rounds an edge's points onto the grid, as `snap_to_grid` did its nodes and
+// bend points. Its ends are rounded towards the centres of the nodes they leave
+// and enter, so they stay on the cards they were worked out from rather than
+// pulling up to half a grid step clear of them.
+fn snap_edge_to_grid(
+    edge: &mut AssetLayoutEdge,
+    grid: f32,
+    from_node: &GraphNode,
+    to_node: &GraphNode,
+) {
+    let snap = |value: f32| (value / grid).round() * grid;
+    let snap_towards = |value: f32, centre: f32| {
+        if value < centre {
+            (value / grid).ceil() * grid
+        } else {
+            (value / grid).floor() * grid
+        }
+    };
+    edge.from = IPoint {
+        x: snap_towards(edge.from.x, from_node.x),
+        y: snap_towards(edge.from.y, from_node.y),
+    };
+    edge.to = IPoint {
+        x: snap_towards(edge.to.x, to_node.x),
+        y: snap_towards(edge.to.y, to_node.y),
+    };
+    let last = edge.points.len().saturating_sub(1);
+    for (i, point) in edge.points.iter_mut().enumerate() {
+        *point = match i {
+            0 => edge.from.clone(),
+            i if i == last => edge.to.clone(),
+            _ => IPoint {
+                x: snap(point.x),
+                y: snap(point.y),
+            },
+        };
+    }
+    if let Some(label) = edge.label.as_mut() {
+        label.x = snap(label.x);
+        label.y = snap(label.y);
+    }
+}
+
+// Moves the nodes in `fixed` so they are centred on the given points, in a
+// graph that has already been laid out. Edges touching a moved node keep their
+// bend points and get new end points where they meet the node's box. Nothing
+// else moves to make room, so the points should be ones other nodes have left.
+fn move_nodes(g: &mut Graph<GraphConfig, GraphNode, GraphEdge>, fixed: &HashMap<String, IPoint>) {
+    for (id, position) in fixed {
+        if let Some(node) = g.node_mut(id) {
+            node.x = position.x;
+            node.y = position.y;
+        }
+    }
+
+    for e in g.edges() {
+        if e.v == e.w || !(fixed.contains_key(&e.v) || fixed.contains_key(&e.w)) {
+            continue;
+        }
+        let (Some(v_node), Some(w_node)) = (g.node(&e.v).cloned(), g.node(&e.w).cloned()) else {
+            continue;
+        };
+        let Some(edge) = g.edge_mut_with_obj(&e) else {
+            continue;
+        };
+
+        let points = edge.points.clone().unwrap_or_default();
+        let bends: Vec<GraphEdgePoint> = if points.len() > 2 {
+            points[1..points.len() - 1].to_vec()
+        } else {
+            vec![]
+        };
+        let center = |node: &GraphNode| GraphEdgePoint {
+            x: node.x,
+            y: node.y,
+        };
+        let towards_v = bends.first().cloned().unwrap_or_else(|| center(&w_node));
+        let towards_w = bends.last().cloned().unwrap_or_else(|| center(&v_node));
+
+        let mut rerouted = vec![node_intersect(&v_node, &towards_v)];
+        rerouted.extend(bends);
+        rerouted.push(node_intersect(&w_node, &towards_w));
+        edge.points = Some(rerouted);
+    }
+}
+
+// Folds `other` into `edge` for two edges dagre lays out as one: the larger
+// minlen, the summed weight and the larger label. Unset hints count as 1,
+// dagre's default.
+fn merge_edge_hints(edge: &mut GraphEdge, other: &GraphEdge) {
+    edge.minlen = Some(edge.minlen.unwrap_or(1.0).max(other.minlen.unwrap_or(1.0)));
+    edge.weight = Some(edge.weight.unwrap_or(1.0) + other.weight.unwrap_or(1.0));
+    let larger = |a: Option<f32>, b: Option<f32>| match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    };
+    edge.width = larger(edge.width, other.width);
+    edge.height = larger(edge.height, other.height);
+    if edge.labelpos.is_none() {
+        edge.labelpos = other.labelpos.clone();
+    }
+}
+
+// The box dagre placed `node` in, from its centre and size.
+fn dagre_node_bounds(node: &GraphNode) -> IBounds {
+    IBounds {
+        x: node.x - node.width / 2.0,
+        y: node.y - node.height / 2.0,
+        width: node.width,
+        height: node.height,
+    }
+}
+
+// Where a line from `point` to the centre of `node` crosses the node's border.
+// `intersect_rect` panics for a point at the centre itself, so that case
+// returns the centre.
+fn node_intersect(node: &GraphNode, point: &GraphEdgePoint) -> GraphEdgePoint {
+    if point.x == node.x && point.y == node.y {
+        return point.clone();
+    }
+    let rect = Rect {
+        x: node.x,
+        y: node.y,
+        width: node.width,
+        height: node.height,
+    };
+    intersect_rect(&rect, point)
+}
+
+pub const SELF_LOOP_WIDTH: f32 = 30.0;
+
+// A rectangular loop leaving the right side of the node in its upper half and
+// coming back in its lower half.
+fn self_loop_edge(id: &GraphId, node: &GraphNode) -> AssetLayoutEdge {
+    let right = node.x + node.width / 2.0;
+    let top = node.y - node.height / 4.0;
+    let bottom = node.y + node.height / 4.0;
+    let from = IPoint { x: right, y: top };
+    let to = IPoint {
+        x: right,
+        y: bottom,
+    };
+    AssetLayoutEdge {
+        points: vec![
+            from.clone(),
+            IPoint {
+                x: right + SELF_LOOP_WIDTH,
+                y: top,
+            },
+            IPoint {
+                x: right + SELF_LOOP_WIDTH,
+                y: bottom,
+            },
+            to.clone(),
+        ],
+        from,
+        fromId: id.clone(),
+        to,
+        toId: id.clone(),
+        label: None,
+        reversed: false,
+    }
+}
+
+// dagre's edge points start and end where the edge meets its nodes; everything
+// in between is a bend point. dagre doubles every edge's minlen to make room for
+// labels, so even neighbouring nodes get a single midpoint, which we drop.
+fn interior_points(edge: &GraphEdge) -> Vec<IPoint> {
+    let points = edge.points.as_deref().unwrap_or_default();
+    if points.len() <= 3 {
+        return vec![];
+    }
+    points[1..points.len() - 1]
+        .iter()
+        .map(|p| IPoint { x: p.x, y: p.y })
+        .collect()
+}
+
+fn asset_layout_kind(node: Option<&AssetGraphNode>, is_external_link: bool) -> String {
+    match node.map(|node| &node.definition) {
+        _ if is_external_link => "link",
+        Some(def) if def.isSource && def.isObservable => "observable_source",
+        Some(def) if def.isSource => "source",
+        _ => "asset",
+    }
+    .to_string()
+}
+
+pub const ASSET_LINK_NAME_MAX_LENGTH: usize = 10;
+
+pub fn get_asset_link_dimensions(label: &str, opts: &LayoutAssetGraphOptions) -> IBounds {
+    if opts.rankdir().is_horizontal() {
+        IBounds {
+            x: 0.0,
+            y: 0.0,
+            width: 32.0 + 8.0 * std::cmp::min(opts.link_name_max_length, label.len()) as f32,
+            height: opts.link_height,
+        }
+    } else {
+        IBounds {
+            x: 0.0,
+            y: 0.0,
+            width: 106.0,
+            height: opts.link_height,
+        }
+    }
+}
+
+pub fn pad_bounds(a: &IBounds, padding: &IPoint) -> IBounds {
+    IBounds {
+        x: a.x - padding.x,
+        y: a.y - padding.y,
+        width: a.width + padding.x * 2.0,
+        height: a.height + padding.y * 2.0,
+    }
+}
+
+pub fn extend_bounds(a: &IBounds, b: &IBounds) -> IBounds {
+    let xmin = a.x.min(b.x);
+    let ymin = a.y.min(b.y);
+    let xmax = (a.x + a.width).max(b.x + b.width);
+    let ymax = (a.y + a.height).max(b.y + b.height);
+    IBounds {
+        x: xmin,
+        y: ymin,
+        width: xmax - xmin,
+        height: ymax - ymin,
+    }
+}
+
+fn sort_edges(edges: &mut [AssetLayoutEdge]) {
+    edges.sort_by(|a, b| (&a.fromId, &a.toId).cmp(&(&b.fromId, &b.toId)));
+}
+
+// Counts pairs of segments, from different edges, that cross. dagre counts
+// crossings while ordering, but before order hints move nodes and without
+// keeping the result, so this works from the final polylines instead.
+fn count_edge_crossings(edges: &[AssetLayoutEdge]) -> usize {
+    let segments: Vec<Vec<(&IPoint, &IPoint)>> = edges
+        .iter()
+        .filter(|edge| edge.fromId != edge.toId)
+        .map(|edge| {
+            edge.points
+                .windows(2)
+                .map(|pair| (&pair[0], &pair[1]))
+                .collect()
+        })
+        .collect();
+
+    let mut crossings = 0;
+    for (i, a) in segments.iter().enumerate() {
+        for b in &segments[i + 1..] {
+            crossings += a
+                .iter()
+                .flat_map(|sa| b.iter().map(move |sb| (sa, sb)))
+                .filter(|((a1, a2), (b1, b2))| segments_cross(a1, a2, b1, b2))
+                .count();
+        }
+    }
+    crossings
+}
+
+// Replaces each segment of `points` that passes through one of `obstacles` with
+// a detour down the nearer side of the box, `ROUTE_MARGIN` clear of it.
+// Detours are re-checked against the other boxes, up to a fixed number of
+// times, so crowded layouts can still be left with a crossing. Bend points
+// inside a box, which a group's padding can reach over, are dropped first,
+// since no detour gets to them without going in.
+fn route_around(points: &[IPoint], obstacles: &[&IBounds]) -> Vec<IPoint> {
+    const ROUTE_MARGIN: f32 = 10.0;
+    let inside = |point: &IPoint| {
+        obstacles.iter().any(|b| {
+            point.x > b.x && point.x < b.x + b.width && point.y > b.y && point.y < b.y + b.height
+        })
+    };
+    let last = points.len().saturating_sub(1);
+    let mut points: Vec<IPoint> = points
+        .iter()
+        .enumerate()
+        .filter(|(i, point)| *i == 0 || *i == last || !inside(point))
+        .map(|(_, point)| point.clone())
+        .collect();
+    let mut detours_left = obstacles.len() * 4;
+    'search: while detours_left > 0 {
+        for i in 0..points.len().saturating_sub(1) {
+            let (p, q) = (&points[i], &points[i + 1]);
+            let Some(obstacle) = obstacles.iter().find(|b| segment_hits_box(p, q, b)) else {
+                continue;
+            };
+            let detour = detour_points(p, q, obstacle, ROUTE_MARGIN);
+            points.splice(i + 1..i + 1, detour);
+            detours_left -= 1;
+            continue 'search;
+        }
+        break;
+    }
+    // A bend point just outside a box can leave a detour running out to it
+    // and straight back. Drop such spikes.
+    let mut i = 1;
+    while i + 1 < points.len() {
+        let (p, r) = (&points[i - 1], &points[i + 1]);
+        if p.x == r.x && p.y == r.y {
+            points.drain(i..i + 2);
+            i = i.saturating_sub(1).max(1);
+        } else {
+            i += 1;
+        }
+    }
+    points
+}
+
+// Two bend points taking the segment from `p` to `q` past `b`: along whichever
+// side of the box is on the way, measured across the segment's main
+// direction, then back onto its line past the far side.
+fn detour_points(p: &IPoint, q: &IPoint, b: &IBounds, margin: f32) -> [IPoint; 2] {
+    // Work in (along, across) coordinates, where `along` is the axis the
+    // segment mostly travels on.
+    let vertical = (q.y - p.y).abs() >= (q.x - p.x).abs();
+    let split = |pt: &IPoint| if vertical { (pt.y, pt.x) } else { (pt.x, pt.y) };
+    let join = |along: f32, across: f32| {
+        if vertical {
+            IPoint {
+                x: across,
+                y: along,
+            }
+        } else {
+            IPoint {
+                x: along,
+                y: across,
+            }
+        }
+    };
+    let (along_min, along_max, across_min, across_max) = if vertical {
+        (b.y, b.y + b.height, b.x, b.x + b.width)
+    } else {
+        (b.x, b.x + b.width, b.y, b.y + b.height)
+    };
+    let ((p_along, p_across), (q_along, q_across)) = (split(p), split(q));
+
+    // An end beside the box has to stay on its side; otherwise take the side
+    // nearer the middle of the segment.
+    let beside = |along: f32, across: f32| {
+        (along > along_min && along < along_max).then_some(across < (across_min + across_max) / 2.0)
+    };
+    let before = beside(p_along, p_across)
+        .or(beside(q_along, q_across))
+        .unwrap_or((p_across + q_across) / 2.0 < (across_min + across_max) / 2.0);
+    let across = if before {
+        across_min - margin
+    } else {
+        across_max + margin
+    };
+
+    let (entry, exit) = if q_along >= p_along {
+        (
+            (along_min - margin).max(p_along),
+            (along_max + margin).min(q_along),
+        )
+    } else {
+        (
+            (along_max + margin).min(p_along),
+            (along_min - margin).max(q_along),
+        )
+    };
+    [join(entry, across), join(exit, across)]
+}
+
+// Whether the segment passes through the inside of `b`. Segments that only
+// run along its edge don't count.
+fn segment_hits_box(p: &IPoint, q: &IPoint, b: &IBounds) -> bool {
+    // Liang-Barsky: clip the segment's parameter range to the box.
+    let (dx, dy) = (q.x - p.x, q.y - p.y);
+    let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+    for (delta, low, high) in [
+        (dx, b.x - p.x, b.x + b.width - p.x),
+        (dy, b.y - p.y, b.y + b.height - p.y),
+    ] {
+        if delta == 0.0 {
+            if low >= 0.0 || high <= 0.0 {
+                return false;
+            }
+            continue;
+        }
+        let (a, c) = (low / delta, high / delta);
+        t0 = t0.max(a.min(c));
+        t1 = t1.min(a.max(c));
+    }
+    t0 < t1
+}
+
+// Whether the segments cross at a point inside both of them. Segments that
+// only touch, or that overlap along a line, don't.
+fn segments_cross(a1: &IPoint, a2: &IPoint, b1: &IPoint, b2: &IPoint) -> bool {
+    let side = |p: &IPoint, q: &IPoint, r: &IPoint| {
+        let turn = (q.x - p.x) * (r.y - p.y) - (q.y - p.y) * (r.x - p.x);
+        if turn > 0.0 {
+            1
+        } else if turn < 0.0 {
+            -1
+        } else {
+            0
+        }
+    };
+    side(a1, a2, b1) * side(a1, a2, b2) < 0 && side(b1, b2, a1) * side(b1, b2, a2) < 0
+}
+
+// Pads a group's box around its members, with the header padding on the side
+// the header goes: the top, or the left in horizontal layouts.
+fn pad_group_bounds(bounds: &IBounds, padding: &IPoint, rankdir: RankDir) -> IBounds {
+    if !rankdir.is_horizontal() {
+        return pad_bounds(bounds, padding);
+    }
+    IBounds {
+        x: bounds.x - padding.y,
+        y: bounds.y - padding.x,
+        width: bounds.width + padding.y + padding.x,
+        height: bounds.height + padding.x * 2.0,
+    }
+}
+
+/// The top `size` of `bounds`, or the left `size` if `on_left`, clamped to
+/// the box.
+fn group_header_bounds(bounds: &IBounds, size: f32, on_left: bool) -> IBounds {
+    if on_left {
+        IBounds {
+            width: size.min(bounds.width),
+            ..bounds.clone()
+        }
+    } else {
+        IBounds {
+            height: size.min(bounds.height),
+            ..bounds.clone()
+        }
+    }
+}
+
+// A header across the top spans the full width of the group's box; one down
+// the left side is only as wide as the header padding.
+fn header_on_left(group: &GroupLayout) -> bool {
+    group.header_bounds.width < group.bounds.width
+}
+
+fn parent_node_id_for_node(node: &AssetGraphNode) -> String {
+    format!(
+        "{}__{}__{}__{}",
+        GROUP_NODE_PREFIX,
+        node.definition.repository.location.name,
+        node.definition.repository.name,
+        node.definition.groupName.clone().unwrap_or_default()
+    )
+}
+
+// The group an asset is drawn in, if it has one.
+fn group_for_node(node: &AssetGraphNode) -> Option<String> {
+    node.definition
+        .groupName
+        .as_ref()
+        .map(|_| parent_node_id_for_node(node))
+}
+
+/// Ids of assets whose bounds reach outside the box of the group they belong
+/// to, sorted. Groups that weren't drawn are skipped. A correct layout returns
+/// nothing; this is for catching regressions in how group bounds are built.
+pub fn validate_group_containment(layout: &AssetGraphLayout) -> Vec<GraphId> {
+    // Allows for float noise from padding.
+    const EPSILON: f32 = 0.5;
+    let mut escaping: Vec<GraphId> = layout
+        .nodes
+        .values()
+        .filter(|node| {
+            let Some(group) = node.group.as_ref().and_then(|id| layout.groups.get(id)) else {
+                return false;
+            };
+            let (inner, outer) = (&node.bounds, &group.bounds);
+            outer.width > 0.0
+                && (inner.x < outer.x - EPSILON
+                    || inner.y < outer.y - EPSILON
+                    || inner.x + inner.width > outer.x + outer.width + EPSILON
+                    || inner.y + inner.height > outer.y + outer.height + EPSILON)
+        })
+        .map(|node| node.id.clone())
+        .collect();
+    escaping.sort();
+    escaping
+}
+
+pub fn get_collapsed_group_dimensions(
+    opts: &LayoutAssetGraphOptions,
+    members: &[&AssetNode],
+) -> IBounds {
+    // As wide as the group would be with its widest member card inside the
+    // usual group padding, tall enough for just the header.
+    let widest_member = members
+        .iter()
+        .map(|def| asset_node_dimensions(opts, def).width)
+        .fold(0.0, f32::max);
+    IBounds {
+        x: 0.0,
+        y: 0.0,
+        width: widest_member + opts.group_padding.x * 2.0,
+        height: opts.group_padding.y,
+    }
+}
+
+// The size of an asset's card, from `node_dimensions_fn` when one is set.
+fn asset_node_dimensions(opts: &LayoutAssetGraphOptions, def: &AssetNode) -> IBounds {
+    match &opts.node_dimensions_fn {
+        Some(NodeDimensionsFn(dimensions_fn)) => dimensions_fn(def),
+        None => get_asset_node_dimensions(def),
+    }
+}
+
+pub const ASSET_NODE_NAME_MAX_LENGTH: usize = 28;
+
+pub fn get_asset_node_dimensions(def: &AssetNode) -> IBounds {
+    let width: f32 = 265.0;
+
+    let height: f32 = match (def.isSource, def.isObservable) {
+        // Plain sources are never materialized, so there's nothing to report
+        // beneath the name.
+        (true, false) => 102.0,
+        // Observable sources show when they were last observed where other
+        // assets show their status.
+        (true, true) => {
+            100.0 // top tags area + name + description
+                + 30.0 // last observed
+                + 30.0 // tags beneath
+        }
+        (false, _) => {
+            let mut height: f32 = 100.0; // top tags area + name + description
+            height += 26.0; // status row
+            if def.isPartitioned {
+                height += match def.partitionDimensions {
+                    Some(dimensions) => 20.0 * dimensions as f32,
+                    None => 40.0,
+                };
+            }
+            height + 30.0 // tags beneath
+        }
+    };
+
+    IBounds {
+        x: 0.0,
+        y: 0.0,
+        width,
+        height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dagre_rust::layout::util::unique_id;
+
+    fn node(id: &str, group: &str) -> AssetGraphNode {
+        let asset_key = AssetKey {
+            path: vec![id.to_string()],
+        };
+        AssetGraphNode {
+            id: id.to_string(),
+            assetKey: asset_key.clone(),
+            definition: AssetNode {
+                id: id.to_string(),
+                groupName: Some(group.to_string()),
+                opNames: vec!["op".to_string()],
+                repository: Repository {
+                    name: "repo".to_string(),
+                    location: RepositoryLocation {
+                        name: "loc".to_string(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                assetKey: asset_key,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// A graph of `(id, group)` assets joined by `(from, to)` edges.
+    fn graph_data(nodes: &[(&str, &str)], edges: &[(&str, &str)]) -> GraphData {
+        let mut data = GraphData {
+            nodes: nodes
+                .iter()
+                .map(|(id, group)| (id.to_string(), node(id, group)))
+                .collect(),
+            ..Default::default()
+        };
+        for (from, to) in edges {
+            data.downstream
+                .entry(from.to_string())
+                .or_default()
+                .insert(to.to_string(), true);
+            data.upstream
+                .entry(to.to_string())
+                .or_default()
+                .insert(from.to_string(), true);
+        }
+        data
+    }
+
+    #[test]
+    fn group_layout_serializes_in_camel_case() {
+        let data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b")]);
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        let json = serde_json::to_value(&layout).unwrap();
+        let group = json["groups"].as_object().unwrap().values().next().unwrap();
+        assert_eq!(group["memberIds"], serde_json::json!(["a", "b"]));
+        assert!(group.get("headerBounds").is_some());
+        assert!(group.get("member_ids").is_none());
+    }
+
+    #[test]
+    fn diff_reports_only_the_shifted_node() {
+        let data = graph_data(&[("a", "g"), ("b", "g"), ("c", "g")], &[("a", "b")]);
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        let mut shifted = layout.clone();
+        shifted.nodes.get_mut("c").unwrap().bounds.x += 40.0;
+        // Float noise under the threshold doesn't count as a move.
+        shifted.nodes.get_mut("a").unwrap().bounds.y += 0.1;
+
+        let diff = layout.diff(&shifted);
+        assert_eq!(diff.moved.len(), 1);
+        assert_eq!(diff.moved[0].id, "c");
+        assert_eq!(
+            diff.moved[0].new_bounds.x,
+            diff.moved[0].old_bounds.x + 40.0
+        );
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert!(diff.changed_edges.is_empty());
+
+        let json = serde_json::to_value(&diff).unwrap();
+        assert!(json.get("addedEdges").is_some());
+        assert!(json["moved"][0].get("oldBounds").is_some());
+    }
+
+    #[test]
+    fn example_fixture_lays_out() {
+        // What `examples/layout.rs` prints for `small_graph.json`.
+        let output = layout_asset_graph_json(include_str!("../small_graph.json"));
+        let layout: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert!(layout["width"].as_f64().unwrap() > 0.0);
+        assert!(layout["height"].as_f64().unwrap() > 0.0);
+        assert!(!layout["nodes"].as_object().unwrap().is_empty());
+    }
+
+    #[test]
+    fn reversed_edge_keeps_its_hints_when_merged() {
+        let gap = |hinted: Option<(&str, &str)>| {
+            let mut data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b"), ("b", "a")]);
+            if let Some((from, to)) = hinted {
+                data.edgeHints.entry(from.to_string()).or_default().insert(
+                    to.to_string(),
+                    EdgeHints {
+                        minlen: 3,
+                        weight: 1,
+                    },
+                );
+            }
+            let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+            (layout.nodes["a"].bounds.y - layout.nodes["b"].bounds.y).abs()
+        };
+        // One of the two edges is reversed onto the other. Either way the
+        // minlen hint has to survive.
+        let plain = gap(None);
+        assert_eq!(gap(Some(("a", "b"))), gap(Some(("b", "a"))));
+        assert!(gap(Some(("a", "b"))) > plain);
+    }
+
+    #[test]
+    fn collapsed_group_stands_in_for_its_members() {
+        let data = graph_data(
+            &[("a", "g1"), ("b", "g1"), ("c", "g2")],
+            &[("a", "b"), ("b", "c")],
+        );
+        let collapsed = parent_node_id_for_node(&data.nodes["a"]);
+        let opts = LayoutAssetGraphOptions::builder()
+            .collapsed_groups(HashSet::from([collapsed.clone()]))
+            .build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+
+        assert_eq!(layout.nodes.keys().collect::<Vec<_>>(), vec!["c"]);
+        let edges: Vec<_> = layout
+            .edges
+            .iter()
+            .map(|edge| (edge.fromId.as_str(), edge.toId.as_str()))
+            .collect();
+        assert_eq!(edges, vec![(collapsed.as_str(), "c")]);
+
+        let group = &layout.groups[&collapsed];
+        assert_eq!(group.member_ids, vec!["a", "b"]);
+        let padding = opts.group_padding;
+        assert_eq!(group.bounds.width, 265.0 + padding.x * 2.0);
+        assert_eq!(group.bounds.height, padding.y);
+    }
+
+    #[test]
+    fn collapsed_group_lays_out_beside_an_expanded_one() {
+        let data = graph_data(
+            &[("a", "g1"), ("b", "g1"), ("c", "g2"), ("d", "g2")],
+            &[("a", "c"), ("b", "d"), ("c", "d")],
+        );
+        let collapsed = parent_node_id_for_node(&data.nodes["a"]);
+        let expanded = parent_node_id_for_node(&data.nodes["c"]);
+        let opts = LayoutAssetGraphOptions::builder()
+            .collapsed_groups(HashSet::from([collapsed.clone()]))
+            .node_dimensions(|_| IBounds {
+                x: 0.0,
+                y: 0.0,
+                width: 400.0,
+                height: 100.0,
+            })
+            .build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+
+        assert_eq!(
+            layout.groups[&collapsed].bounds.width,
+            400.0 + opts.group_padding.x * 2.0
+        );
+        assert_eq!(layout.groups[&expanded].member_ids, vec!["c", "d"]);
+        assert!(validate_group_containment(&layout).is_empty());
+        assert!(!overlaps(
+            &layout.groups[&collapsed].bounds,
+            &layout.groups[&expanded].bounds,
+            0.0
+        ));
+    }
+
+    #[test]
+    fn snap_grid_puts_nodes_and_edges_on_the_grid() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g"), ("d", "g")],
+            &[("a", "b"), ("a", "c"), ("b", "d"), ("c", "d"), ("a", "d")],
+        );
+        for rankdir in [RankDir::TB, RankDir::LR] {
+            let opts = LayoutAssetGraphOptions::builder()
+                .rankdir(rankdir)
+                .snap_grid(10.0)
+                .build();
+            let layout = layout_asset_graph(&data, &opts).unwrap();
+            let on_grid = |value: f32| value % 10.0 == 0.0;
+            let touches = |bounds: &IBounds, point: &IPoint, slack: f32| {
+                (bounds.x - slack..=bounds.x + bounds.width + slack).contains(&point.x)
+                    && (bounds.y - slack..=bounds.y + bounds.height + slack).contains(&point.y)
+            };
+            for node in layout.nodes.values() {
+                assert!(on_grid(node.bounds.x) && on_grid(node.bounds.y));
+            }
+            for edge in &layout.edges {
+                for point in edge.points.iter().chain([&edge.from, &edge.to]) {
+                    assert!(on_grid(point.x) && on_grid(point.y), "{:?}", point);
+                }
+                // Each end still touches the card it leaves or enters.
+                let from = &layout.nodes[&edge.fromId].bounds;
+                let to = &layout.nodes[&edge.toId].bounds;
+                assert!(touches(from, &edge.from, 0.0));
+                assert!(touches(to, &edge.to, opts.arrow_gap));
+            }
+        }
+    }
+
+    #[test]
+    fn pinning_a_node_keeps_its_rank_and_moves_its_edges() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g"), ("d", "g")],
+            &[("a", "b"), ("a", "c"), ("d", "c")],
+        );
+        for rankdir in [RankDir::TB, RankDir::LR] {
+            let opts = LayoutAssetGraphOptions::builder().rankdir(rankdir).build();
+            let ranked = RankedAssetGraph::new(&data, &opts).unwrap();
+            let before = ranked.layout();
+            // Coordinates along the rank, then across it.
+            let split = |point: &IPoint| match rankdir.is_horizontal() {
+                true => (point.y, point.x),
+                false => (point.x, point.y),
+            };
+            let centre = |layout: &AssetGraphLayout, id: &str| {
+                let bounds = &layout.nodes[id].bounds;
+                IPoint {
+                    x: bounds.x + bounds.width / 2.0,
+                    y: bounds.y + bounds.height / 2.0,
+                }
+            };
+            let from_a = |layout: &AssetGraphLayout, to: &str| {
+                let edge = layout
+                    .edges
+                    .iter()
+                    .find(|edge| edge.fromId == "a" && edge.toId == to);
+                split(&edge.unwrap().from)
+            };
+
+            let unpinned = ranked.relayout_preserving_ranks(&HashMap::new()).unwrap();
+            let bounds = |layout: &AssetGraphLayout, id: &str| {
+                let bounds = &layout.nodes[id].bounds;
+                (bounds.x, bounds.y, bounds.width, bounds.height)
+            };
+            for id in ["a", "b", "c", "d"] {
+                assert_eq!(bounds(&unpinned, id), bounds(before, id));
+            }
+
+            let (along, across) = split(&centre(before, "a"));
+            let target = match rankdir.is_horizontal() {
+                true => IPoint {
+                    x: across,
+                    y: along + 300.0,
+                },
+                false => IPoint {
+                    x: along + 300.0,
+                    y: across,
+                },
+            };
+            let pinned = ranked
+                .relayout_preserving_ranks(&HashMap::from([("a".to_string(), target)]))
+                .unwrap();
+
+            assert_eq!(pinned.nodes["a"].rank, before.nodes["a"].rank);
+            assert_eq!(split(&centre(&pinned, "a")), (along + 300.0, across));
+            for to in ["b", "c"] {
+                let (moved, unmoved) = (from_a(&pinned, to), from_a(before, to));
+                assert_eq!(moved.0 - unmoved.0, 300.0);
+                assert_eq!(moved.1, unmoved.1);
+            }
+            let ids = ["a", "b", "c", "d"];
+            for (i, v) in ids.iter().enumerate() {
+                for w in &ids[i + 1..] {
+                    let (v, w) = (&pinned.nodes[*v].bounds, &pinned.nodes[*w].bounds);
+                    assert!(!overlaps(v, w, 0.0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn edge_label_sits_midway_between_its_ends() {
+        let mut data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b")]);
+        let plain = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        data.edgeLabels.entry("a".to_string()).or_default().insert(
+            "b".to_string(),
+            EdgeLabel {
+                width: 100.0,
+                height: 20.0,
+            },
+        );
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+
+        let edge = &layout.edges[0];
+        let label = edge.label.as_ref().unwrap();
+        let midway = IPoint {
+            x: (edge.from.x + edge.to.x) / 2.0,
+            y: (edge.from.y + edge.to.y) / 2.0,
+        };
+        assert!(
+            (label.y - midway.y).abs() <= 20.0,
+            "{:?} {:?}",
+            label,
+            midway
+        );
+        assert!(label.y > edge.from.y && label.y < edge.to.y);
+        // The label's rank pushes the two cards further apart.
+        let gap =
+            |layout: &AssetGraphLayout| layout.nodes["b"].bounds.y - layout.nodes["a"].bounds.y;
+        assert!(gap(&layout) > gap(&plain));
+        assert!(plain.edges[0].label.is_none());
+    }
+
+    #[test]
+    fn rank_overrides_line_nodes_up_on_one_rank() {
+        // Left to dagre, c sits a rank below a.
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g")],
+            &[("a", "b"), ("b", "c")],
+        );
+        let overrides = HashMap::from([("a".to_string(), 1), ("c".to_string(), 1)]);
+        let opts = LayoutAssetGraphOptions::builder()
+            .rank_overrides(overrides)
+            .build();
+        let conflict = layout_asset_graph(&data, &opts);
+        assert!(matches!(conflict, Err(LayoutError::RankConflict(_))));
+
+        let data = graph_data(&[("a", "g"), ("b", "g"), ("c", "g")], &[("a", "b")]);
+        let plain = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        assert_ne!(plain.nodes["b"].bounds.y, plain.nodes["c"].bounds.y);
+        let overrides = HashMap::from([("b".to_string(), 1), ("c".to_string(), 1)]);
+        let opts = LayoutAssetGraphOptions::builder()
+            .rank_overrides(overrides)
+            .build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+        assert_eq!(layout.nodes["b"].bounds.y, layout.nodes["c"].bounds.y);
+        assert_eq!(layout.nodes["b"].rank, layout.nodes["c"].rank);
+    }
+
+    #[test]
+    fn compact_closes_empty_ranks_and_keeps_the_order() {
+        let mut data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g"), ("d", "g")],
+            &[("a", "b"), ("a", "c"), ("c", "d")],
+        );
+        // Three ranks between a and d that hold nothing but the edge.
+        data.edgeHints.entry("c".to_string()).or_default().insert(
+            "d".to_string(),
+            EdgeHints {
+                minlen: 4,
+                weight: 1,
+            },
+        );
+        let sparse = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        let opts = LayoutAssetGraphOptions::builder().compact(true).build();
+        let compact = layout_asset_graph(&data, &opts).unwrap();
+
+        assert!(compact.height < sparse.height);
+        assert_eq!(compact.ordered_node_ids, sparse.ordered_node_ids);
+        // Top to bottom, the assets come in the same order either way.
+        let top_down = |layout: &AssetGraphLayout| {
+            let mut ids: Vec<String> = layout.nodes.keys().cloned().collect();
+            ids.sort_by(|a, b| {
+                let (a_y, b_y) = (layout.nodes[a].bounds.y, layout.nodes[b].bounds.y);
+                a_y.total_cmp(&b_y).then(a.cmp(b))
+            });
+            ids
+        };
+        assert_eq!(top_down(&compact), top_down(&sparse));
+        let d = &compact.nodes["d"].bounds;
+        let edge = compact.edges.iter().find(|edge| edge.toId == "d").unwrap();
+        assert!(edge.to.y >= d.y && edge.to.y <= d.y + d.height);
+    }
+
+    #[test]
+    fn route_around_detours_outside_the_box() {
+        let (from, to) = (IPoint { x: 0.0, y: 0.0 }, IPoint { x: 0.0, y: 100.0 });
+        let obstacle = IBounds {
+            x: -20.0,
+            y: 40.0,
+            width: 50.0,
+            height: 20.0,
+        };
+        let routed = route_around(&[from.clone(), to.clone()], &[&obstacle]);
+
+        assert!(routed.len() > 2);
+        assert_eq!((routed[0].x, routed[0].y), (from.x, from.y));
+        let last = routed.last().unwrap();
+        assert_eq!((last.x, last.y), (to.x, to.y));
+        for bend in &routed[1..routed.len() - 1] {
+            assert!(!overlaps(
+                &obstacle,
+                &IBounds {
+                    x: bend.x,
+                    y: bend.y,
+                    width: 0.0,
+                    height: 0.0
+                },
+                0.0
+            ));
+        }
+        for segment in routed.windows(2) {
+            assert!(!segment_hits_box(&segment[0], &segment[1], &obstacle));
+        }
+    }
+
+    #[test]
+    fn edges_route_around_groups_they_pass_through() {
+        let data = graph_data(
+            &[("a", "g1"), ("b", "g2"), ("c", "g3")],
+            &[("a", "b"), ("b", "c"), ("a", "c")],
+        );
+        let crossings = |layout: &AssetGraphLayout| {
+            let middle = &layout.groups[&parent_node_id_for_node(&data.nodes["b"])].bounds;
+            let edge = layout
+                .edges
+                .iter()
+                .find(|edge| edge.fromId == "a" && edge.toId == "c");
+            let points = &edge.unwrap().points;
+            points
+                .windows(2)
+                .filter(|segment| segment_hits_box(&segment[0], &segment[1], middle))
+                .count()
+        };
+        let straight = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        assert!(crossings(&straight) > 0);
+
+        let opts = LayoutAssetGraphOptions::builder()
+            .route_around_groups(true)
+            .build();
+        let routed = layout_asset_graph(&data, &opts).unwrap();
+        assert_eq!(crossings(&routed), 0);
+    }
+
+    #[test]
+    fn apply_delta_puts_a_new_leaf_beside_its_neighbor() {
+        let data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b")]);
+        let mut layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        let grown = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g")],
+            &[("a", "b"), ("b", "c")],
+        );
+        layout.apply_delta(&[grown.nodes["c"].clone()], &[], &grown);
 
-    let mut max_width = 0;
-    let mut max_height = 0;
+        let (b, c) = (&layout.nodes["b"].bounds, &layout.nodes["c"].bounds);
+        // One rank below b, centred under it.
+        assert_eq!(c.y, b.y + b.height + 50.0);
+        assert_eq!(c.x + c.width / 2.0, b.x + b.width / 2.0);
+        assert_eq!(layout.nodes["c"].rank, Some(2));
+        assert!(layout.height as f32 >= c.y + c.height);
+        assert!(layout
+            .edges
+            .iter()
+            .any(|edge| edge.fromId == "b" && edge.toId == "c"));
 
-    for id in g.nodes() {
-        if let Some(dagre_node) = g.node(&id) {
-            let bounds = IBounds {
-                x: dagre_node.x - dagre_node.width / 2.0,
-                y: dagre_node.y - dagre_node.height / 2.0,
-                width: dagre_node.width,
-                height: dagre_node.height,
-            };
-            if !id.starts_with(GROUP_NODE_PREFIX) {
-                let id_copy = id.clone();
-                nodes.insert(
-                    id,
-                    AssetLayout {
-                        id: id_copy,
-                        bounds: bounds.clone(),
-                    },
-                );
-            }
-            max_width = max_width.max((dagre_node.x + dagre_node.width / 2.0).round() as i32);
-            max_height = max_height.max((dagre_node.y + dagre_node.height / 2.0).round() as i32);
+        layout.apply_delta(&[], &["a".to_string()], &grown);
+        assert!(!layout.nodes.contains_key("a"));
+        assert!(layout.edges.iter().all(|edge| edge.fromId != "a"));
+        assert!(!layout.ordered_node_ids.contains(&"a".to_string()));
+    }
+
+    #[test]
+    fn graph_data_deserializes_from_the_fixture() {
+        let args: LayoutAssetGraphArgs =
+            serde_json::from_str(include_str!("../small_graph.json")).unwrap();
+        let data = args.graphData;
+        assert_eq!(data.nodes.len(), 10);
+        let asset_a = &data.nodes[r#"["asset_a"]"#];
+        assert_eq!(asset_a.assetKey.path, vec!["asset_a"]);
+        assert_eq!(asset_a.definition.groupName.as_deref(), Some("default"));
+        assert_eq!(asset_a.definition.repository.location.name, "toys");
+        assert_eq!(asset_a.definition.graphName, None);
+        assert!(data.downstream[r#"["upstream"]"#][r#"["downstream"]"#]);
+        // Fields the fixture leaves out fall back to their defaults.
+        assert!(data.edgeHints.is_empty());
+        assert!(!args.opts.horizontalDAGs);
+
+        let json = serde_json::to_string(&data).unwrap();
+        let again = GraphData::from_reader(json.as_bytes()).unwrap();
+        assert_eq!(
+            serde_json::to_value(&again).unwrap(),
+            serde_json::to_value(&data).unwrap()
+        );
+    }
+
+    #[test]
+    fn rankdir_sets_which_way_a_chain_flows() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g")],
+            &[("a", "b"), ("b", "c")],
+        );
+        let centers = |rankdir: RankDir| {
+            let opts = LayoutAssetGraphOptions::builder().rankdir(rankdir).build();
+            let layout = layout_asset_graph(&data, &opts).unwrap();
+            ["a", "b", "c"].map(|id| {
+                let bounds = &layout.nodes[id].bounds;
+                (
+                    bounds.x + bounds.width / 2.0,
+                    bounds.y + bounds.height / 2.0,
+                )
+            })
+        };
+
+        let [a, b, c] = centers(RankDir::TB);
+        assert!(a.1 < b.1 && b.1 < c.1);
+        let [a, b, c] = centers(RankDir::BT);
+        assert!(a.1 > b.1 && b.1 > c.1);
+        let [a, b, c] = centers(RankDir::LR);
+        assert!(a.0 < b.0 && b.0 < c.0);
+        let [a, b, c] = centers(RankDir::RL);
+        assert!(a.0 > b.0 && b.0 > c.0);
+
+        let legacy = LayoutAssetGraphOptions {
+            horizontalDAGs: true,
+            ..Default::default()
+        };
+        assert_eq!(legacy.rankdir(), RankDir::LR);
+        assert_eq!(LayoutAssetGraphOptions::default().rankdir(), RankDir::TB);
+    }
+
+    #[test]
+    fn nodesep_and_ranksep_widen_the_gaps() {
+        // a feeds two siblings, so there is a gap along the rank and one
+        // between ranks.
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g")],
+            &[("a", "b"), ("a", "c")],
+        );
+        let gaps = |opts: LayoutAssetGraphOptions| {
+            let layout = layout_asset_graph(&data, &opts).unwrap();
+            let (a, b, c) = (
+                &layout.nodes["a"].bounds,
+                &layout.nodes["b"].bounds,
+                &layout.nodes["c"].bounds,
+            );
+            let (left, right) = if b.x < c.x { (b, c) } else { (c, b) };
+            (right.x - (left.x + left.width), b.y - (a.y + a.height))
+        };
+
+        let (along, between) = gaps(LayoutAssetGraphOptions::default());
+        let (wider_along, same_between) =
+            gaps(LayoutAssetGraphOptions::builder().nodesep(150.0).build());
+        assert!((wider_along - along - 100.0).abs() < 1.0);
+        assert!((same_between - between).abs() < 1.0);
+
+        let (same_along, wider_between) =
+            gaps(LayoutAssetGraphOptions::builder().ranksep(150.0).build());
+        assert!((same_along - along).abs() < 1.0);
+        assert!((wider_between - between - 100.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn group_padding_surrounds_the_members() {
+        // Group boxes are only drawn when there is more than one group.
+        let data = graph_data(&[("a", "g"), ("b", "g"), ("c", "h")], &[("a", "b")]);
+        let padding = IPoint { x: 10.0, y: 40.0 };
+        let opts = LayoutAssetGraphOptions::builder()
+            .group_padding(padding.clone())
+            .build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+        let group = &layout.groups[layout.nodes["a"].group.as_ref().unwrap()];
+        let members = extend_bounds(&layout.nodes["a"].bounds, &layout.nodes["b"].bounds);
+
+        assert!((group.bounds.x - (members.x - padding.x)).abs() < 0.01);
+        assert!((group.bounds.y - (members.y - padding.y)).abs() < 0.01);
+        assert!((group.bounds.width - (members.width + 2.0 * padding.x)).abs() < 0.01);
+        assert!((group.bounds.height - (members.height + 2.0 * padding.y)).abs() < 0.01);
+        assert_eq!(group.header_bounds.height, padding.y);
+    }
+
+    #[test]
+    fn node_dimensions_callback_sizes_the_cards() {
+        let data = graph_data(&[("a", "g"), ("wide", "g")], &[("a", "wide")]);
+        let opts = LayoutAssetGraphOptions::builder()
+            .node_dimensions(|def| IBounds {
+                x: 0.0,
+                y: 0.0,
+                width: if def.id == "wide" { 500.0 } else { 100.0 },
+                height: 40.0,
+            })
+            .build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+        let (a, wide) = (&layout.nodes["a"].bounds, &layout.nodes["wide"].bounds);
+        assert_eq!((a.width, a.height), (100.0, 40.0));
+        assert_eq!((wide.width, wide.height), (500.0, 40.0));
+        assert!(a.y + a.height <= wide.y);
+    }
+
+    #[test]
+    fn long_edges_carry_their_bend_points() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g")],
+            &[("a", "b"), ("b", "c"), ("a", "c")],
+        );
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        for edge in &layout.edges {
+            let (first, last) = (&edge.points[0], edge.points.last().unwrap());
+            assert_eq!((first.x, first.y), (edge.from.x, edge.from.y));
+            assert_eq!((last.x, last.y), (edge.to.x, edge.to.y));
         }
+        // a -> c skips b's rank, so dagre routes it through a bend point
+        // alongside b.
+        let skip = layout
+            .edges
+            .iter()
+            .find(|e| e.fromId == "a" && e.toId == "c")
+            .unwrap();
+        assert!(skip.points.len() > 2);
+        let b = &layout.nodes["b"].bounds;
+        assert!(skip.points[1..skip.points.len() - 1]
+            .iter()
+            .any(|p| p.y > b.y && p.y < b.y + b.height));
     }
 
-    if show_groups {
-        for node in &rendered_nodes {
-            if let Some(group_name) = &node.definition.groupName {
-                let group_id = parent_node_id_for_node(node);
-                let group_for_id = groups.get_mut(&group_id).unwrap();
-                if group_for_id.bounds.width == 0.0 {
-                    group_for_id.bounds = nodes.get(&node.id).unwrap().bounds.clone();
-                } else {
-                    group_for_id.bounds =
-                        extend_bounds(&group_for_id.bounds, &nodes.get(&node.id).unwrap().bounds);
-                }
-            }
+    #[test]
+    fn empty_graphs_lay_out_to_nothing() {
+        for opts in [
+            LayoutAssetGraphOptions::default(),
+            LayoutAssetGraphOptions::builder()
+                .rankdir(RankDir::LR)
+                .pack_components(true)
+                .build(),
+        ] {
+            let layout = layout_asset_graph(&GraphData::default(), &opts).unwrap();
+            assert!(layout.nodes.is_empty() && layout.edges.is_empty());
+            assert!(layout.groups.is_empty());
+            assert!(layout.width >= 0 && layout.height >= 0);
         }
-        for group in groups.values_mut() {
-            group.bounds = pad_bounds(&group.bounds, &IPoint { x: 15.0, y: 70.0 });
+    }
+
+    #[test]
+    fn malformed_link_ids_are_an_error() {
+        // Assets outside the graphed set become link stubs, labelled from
+        // their JSON-encoded asset key path.
+        let mut data = graph_data(&[("a", "g")], &[("a", r#"["elsewhere"]"#)]);
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        assert_eq!(layout.external_links, vec![r#"["elsewhere"]"#]);
+
+        data.downstream
+            .get_mut("a")
+            .unwrap()
+            .insert("not json".to_string(), true);
+        match layout_asset_graph(&data, &LayoutAssetGraphOptions::default()) {
+            Err(LayoutError::MalformedNodeId(id)) => assert_eq!(id, "not json"),
+            other => panic!("expected MalformedNodeId, got {:?}", other.map(|_| ())),
         }
     }
 
-    let mut edges: Vec<AssetLayoutEdge> = Vec::new();
+    #[test]
+    fn upstream_alone_is_enough_for_edges() {
+        let mut data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g")],
+            &[("a", "b"), ("b", "c")],
+        );
+        data.downstream.clear();
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        let edges: Vec<(&str, &str)> = layout
+            .edges
+            .iter()
+            .map(|e| (e.fromId.as_str(), e.toId.as_str()))
+            .collect();
+        assert_eq!(edges, vec![("a", "b"), ("b", "c")]);
+        assert!(layout.nodes["a"].bounds.y < layout.nodes["b"].bounds.y);
 
-    for edge in g.edges() {
-        let v = edge.v;
-        let w = edge.w;
-        let v_node = g.node(&v).unwrap_throw();
-        let w_node = g.node(&w).unwrap_throw();
+        // Listing a dependency in both maps still draws one edge.
+        let both = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b")]);
+        let layout = layout_asset_graph(&both, &LayoutAssetGraphOptions::default()).unwrap();
+        assert_eq!(layout.edges.len(), 1);
+    }
 
-        let v_x_inset = if links_to_assets_outside_graphed_set.contains_key(&v) {
-            16
-        } else {
-            24
-        };
-        let w_x_inset = if links_to_assets_outside_graphed_set.contains_key(&w) {
-            16
-        } else {
-            24
+    #[test]
+    fn js_entry_point_lays_out_graph_data_json() {
+        let (alpha, beta) = (r#"["alpha"]"#, r#"["beta"]"#);
+        let data = graph_data(&[(alpha, "g"), (beta, "g")], &[(alpha, beta)]);
+        let json = serde_json::to_string(&data).unwrap();
+        let layout: serde_json::Value =
+            serde_json::from_str(&layout_graph_data_json(&json, false).unwrap()).unwrap();
+        assert!(layout["nodes"][alpha]["bounds"]["width"].is_number());
+
+        assert!(layout_graph_data_json("{not json", false).is_err());
+    }
+
+    #[test]
+    fn builder_keeps_defaults_for_unset_options() {
+        let opts = LayoutAssetGraphOptions::builder()
+            .horizontal(true)
+            .margin(20)
+            .build();
+        assert!(opts.horizontalDAGs);
+        assert_eq!(opts.rankdir(), RankDir::LR);
+        assert_eq!(opts.margin, 20);
+
+        let defaults = LayoutAssetGraphOptions::default();
+        assert_eq!(opts.nodesep, defaults.nodesep);
+        assert_eq!(opts.ranksep, defaults.ranksep);
+        assert_eq!(opts.link_height, defaults.link_height);
+        assert_eq!(opts.group_padding.y, defaults.group_padding.y);
+        assert_eq!(opts.self_loops, defaults.self_loops);
+        assert!(opts.rankdir.is_none() && opts.node_dimensions_fn.is_none());
+
+        // An explicit rankdir wins over the deprecated flag.
+        let opts = LayoutAssetGraphOptions::builder()
+            .horizontal(true)
+            .rankdir(RankDir::BT)
+            .build();
+        assert_eq!(opts.rankdir(), RankDir::BT);
+    }
+
+    #[test]
+    fn self_loops_get_a_loop_edge_unless_turned_off() {
+        let data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "a"), ("a", "b")]);
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        let self_loop = layout
+            .edges
+            .iter()
+            .find(|e| e.fromId == "a" && e.toId == "a")
+            .unwrap();
+        // The loop leaves and comes back to the same card, bending out past
+        // its side.
+        let a = &layout.nodes["a"].bounds;
+        assert!(self_loop.points.len() > 2);
+        assert!(self_loop.points.iter().any(|p| p.x > a.x + a.width));
+        assert!(!self_loop.reversed);
+
+        let opts = LayoutAssetGraphOptions::builder().self_loops(false).build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+        assert_eq!(layout.edges.len(), 1);
+        assert_eq!(layout.edges[0].toId, "b");
+    }
+
+    #[test]
+    fn layouts_do_not_depend_on_map_order() {
+        // Each HashMap gets its own random seed, so every copy of the graph
+        // iterates its assets in a different order.
+        let nodes: Vec<(String, &str)> = (0..12)
+            .map(|i| (format!("n{}", i), if i % 3 == 0 { "g" } else { "h" }))
+            .collect();
+        let node_refs: Vec<(&str, &str)> = nodes.iter().map(|(id, g)| (id.as_str(), *g)).collect();
+        let edges = [
+            ("n0", "n3"),
+            ("n1", "n3"),
+            ("n2", "n4"),
+            ("n3", "n5"),
+            ("n4", "n5"),
+            ("n4", "n6"),
+            ("n7", "n8"),
+            ("n9", "n10"),
+            ("n5", "n11"),
+        ];
+        let render = || {
+            let data = graph_data(&node_refs, &edges);
+            let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+            serde_json::to_string(&layout).unwrap()
         };
+        let first = render();
+        for _ in 0..5 {
+            assert_eq!(render(), first);
+        }
+    }
 
-        let asset_layout_edge = if opts.horizontalDAGs {
-            AssetLayoutEdge {
-                from: IPoint {
-                    x: v_node.x + v_node.width / 2.0,
-                    y: v_node.y,
-                },
-                fromId: v.clone(),
-                to: IPoint {
-                    x: w_node.x - w_node.width / 2.0 - 5.0,
-                    y: w_node.y,
-                },
-                toId: w.clone(),
+    #[test]
+    fn layouts_do_not_depend_on_dummy_ids() {
+        // dagre_rust numbers dummy nodes from a counter shared by every
+        // layout. Lay the same graph out with the numbers running across a
+        // power of ten, where they sort differently as strings.
+        let nodes: Vec<(String, &str)> = (0..12)
+            .map(|i| (format!("n{}", i), if i % 3 == 0 { "g" } else { "h" }))
+            .collect();
+        let node_refs: Vec<(&str, &str)> = nodes.iter().map(|(id, g)| (id.as_str(), *g)).collect();
+        let data = graph_data(
+            &node_refs,
+            &[
+                ("n0", "n3"),
+                ("n1", "n3"),
+                ("n2", "n4"),
+                ("n3", "n5"),
+                ("n4", "n5"),
+                ("n4", "n6"),
+                ("n7", "n8"),
+                ("n9", "n10"),
+                ("n5", "n11"),
+            ],
+        );
+        let render = || {
+            let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+            serde_json::to_string(&layout).unwrap()
+        };
+        let first = render();
+        for ids_before_boundary in [15, 25, 35] {
+            let mut next = unique_id();
+            let boundary = 10usize.pow(next.ilog10() + 1);
+            while next < boundary - ids_before_boundary {
+                next = unique_id();
             }
-        } else {
-            AssetLayoutEdge {
-                from: IPoint {
-                    x: v_node.x - v_node.width / 2.0 + v_x_inset as f32,
-                    y: v_node.y - 30.0 + v_node.height / 2.0,
-                },
-                fromId: v.clone(),
-                to: IPoint {
-                    x: w_node.x - w_node.width / 2.0 + w_x_inset as f32,
-                    y: w_node.y + 20.0 - w_node.height / 2.0,
-                },
-                toId: w.clone(),
+            assert_eq!(render(), first, "crossing {}", boundary);
+        }
+    }
+
+    #[test]
+    fn ordered_node_ids_go_rank_by_rank() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g"), ("d", "g"), ("e", "g")],
+            &[("a", "b"), ("a", "c"), ("a", "d"), ("c", "e")],
+        );
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        let ids = &layout.ordered_node_ids;
+        assert_eq!(ids.len(), layout.nodes.len());
+        assert_eq!(ids[0], "a");
+        assert_eq!(ids.last().unwrap(), "e");
+        for pair in ids.windows(2) {
+            let (first, second) = (&layout.nodes[&pair[0]], &layout.nodes[&pair[1]]);
+            assert!(first.rank <= second.rank);
+            if first.rank == second.rank {
+                assert!(first.bounds.x < second.bounds.x);
             }
+        }
+        assert_eq!(layout.nodes["e"].rank, Some(2));
+    }
+
+    #[test]
+    fn pack_components_packs_two_chains_tighter() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g"), ("d", "g")],
+            &[("a", "b"), ("c", "d")],
+        );
+        let opts = LayoutAssetGraphOptions::builder()
+            .pack_components(true)
+            .build();
+        let packed = layout_asset_graph(&data, &opts).unwrap();
+        let side_by_side = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+
+        let chain = |layout: &AssetGraphLayout, from: &str, to: &str| {
+            extend_bounds(&layout.nodes[from].bounds, &layout.nodes[to].bounds)
+        };
+        let (first, second) = (chain(&packed, "a", "b"), chain(&packed, "c", "d"));
+        assert!(!overlaps(&first, &second, 0.0));
+        assert!(packed.width < side_by_side.width);
+        assert_eq!(packed.nodes.len(), 4);
+        assert_eq!(packed.edges.len(), 2);
+        for edge in &packed.edges {
+            let to = &packed.nodes[&edge.toId].bounds;
+            assert!(edge.to.x >= to.x && edge.to.x <= to.x + to.width);
+        }
+    }
+
+    #[test]
+    fn link_name_max_length_widens_horizontal_stubs() {
+        let link = r#"["a_rather_long_asset_name"]"#;
+        let data = graph_data(&[("a", "g")], &[("a", link)]);
+        let stub_width = |opts: LayoutAssetGraphOptions| {
+            layout_asset_graph(&data, &opts).unwrap().nodes[link]
+                .bounds
+                .width
         };
 
-        edges.push(asset_layout_edge);
+        let default = stub_width(LayoutAssetGraphOptions::builder().horizontal(true).build());
+        assert_eq!(default, 32.0 + 8.0 * 10.0);
+        let longer = stub_width(
+            LayoutAssetGraphOptions::builder()
+                .horizontal(true)
+                .link_name_max_length(20)
+                .build(),
+        );
+        assert_eq!(longer, 32.0 + 8.0 * 20.0);
+        // Short labels and vertical stubs don't grow.
+        let opts = LayoutAssetGraphOptions::builder()
+            .horizontal(true)
+            .link_name_max_length(20)
+            .build();
+        assert_eq!(
+            get_asset_link_dimensions("short", &opts).width,
+            32.0 + 8.0 * 5.0
+        );
+        let vertical = stub_width(
+            LayoutAssetGraphOptions::builder()
+                .link_name_max_length(20)
+                .build(),
+        );
+        assert_eq!(vertical, 106.0);
     }
 
-    serde_json::to_string(&AssetGraphLayout {
-        width: max_width + MARGIN,
-        height: max_height + MARGIN,
-        edges,
-        nodes,
-        groups,
-    })
-    .unwrap_throw()
-}
+    #[test]
+    fn heavier_edges_are_kept_shorter() {
+        // x hangs between a on the first rank and d on the fourth; whichever
+        // of its edges weighs more is kept to one rank.
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g"), ("d", "g"), ("x", "g")],
+            &[("a", "b"), ("b", "c"), ("c", "d"), ("a", "x"), ("x", "d")],
+        );
+        let rank_of_x = |heavy: (&str, &str)| {
+            let mut data = data.clone();
+            data.edgeHints
+                .entry(heavy.0.to_string())
+                .or_default()
+                .insert(
+                    heavy.1.to_string(),
+                    EdgeHints {
+                        weight: 10,
+                        ..Default::default()
+                    },
+                );
+            let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+            assert_eq!(layout.nodes["d"].rank, Some(3));
+            layout.nodes["x"].rank
+        };
+        assert_eq!(rank_of_x(("a", "x")), Some(1));
+        assert_eq!(rank_of_x(("x", "d")), Some(2));
+    }
 
-pub const ASSET_LINK_NAME_MAX_LENGTH: usize = 10;
+    #[test]
+    fn ranking_keeps_the_fixtures_edges_short() {
+        // The most each fixture's edges may span in total, in ranks. Longest-path
+        // ranks tightened into a feasible tree, which upstream's network simplex
+        // leaves as they are, span 3636 in huge_graph_2; `shorten_heavy_edges`
+        // brings that down to 3588.
+        for (args, most) in [
+            (include_str!("../small_graph.json"), 2),
+            (include_str!("../huge_graph_2.json"), 3588),
+        ] {
+            let args: LayoutAssetGraphArgs = serde_json::from_str(args).unwrap();
+            let layout = layout_asset_graph(&args.graphData, &args.opts).unwrap();
+            let rank = |id: &GraphId| layout.nodes.get(id).and_then(|node| node.rank);
+            let span: i32 = layout
+                .edges
+                .iter()
+                .filter_map(|edge| Some((rank(&edge.toId)? - rank(&edge.fromId)?).abs()))
+                .sum();
+            assert!(span <= most, "{} > {}", span, most);
+        }
+    }
 
-pub fn get_asset_link_dimensions(label: &str, opts: &LayoutAssetGraphOptions) -> IBounds {
-    if opts.horizontalDAGs {
-        IBounds {
-            x: 0.0,
-            y: 0.0,
-            width: 32.0 + 8.0 * std::cmp::min(ASSET_LINK_NAME_MAX_LENGTH, label.len()) as f32,
-            height: 90.0,
+    #[test]
+    fn order_hint_swaps_siblings() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g"), ("d", "g")],
+            &[("a", "b"), ("a", "c"), ("a", "d")],
+        );
+        let left_to_right = |hints: &[(&str, f32)]| {
+            let hints = hints
+                .iter()
+                .map(|(id, hint)| (id.to_string(), *hint))
+                .collect();
+            let opts = LayoutAssetGraphOptions::builder().order_hint(hints).build();
+            let layout = layout_asset_graph(&data, &opts).unwrap();
+            let mut ids = vec!["b", "c", "d"];
+            ids.sort_by(|x, y| {
+                layout.nodes[*x]
+                    .bounds
+                    .x
+                    .total_cmp(&layout.nodes[*y].bounds.x)
+            });
+            ids
+        };
+        let unhinted = left_to_right(&[]);
+        assert_eq!(
+            left_to_right(&[("b", 1.0), ("c", 2.0), ("d", 3.0)]),
+            vec!["b", "c", "d"]
+        );
+        assert_eq!(
+            left_to_right(&[("b", 3.0), ("c", 2.0), ("d", 1.0)]),
+            vec!["d", "c", "b"]
+        );
+        // Hinting only some of them swaps those between their own slots.
+        let partial = left_to_right(&[("b", 2.0), ("d", 1.0)]);
+        let slot = |ids: &[&str], id: &str| ids.iter().position(|x| *x == id).unwrap();
+        assert_eq!(slot(&partial, "c"), slot(&unhinted, "c"));
+        assert!(slot(&partial, "d") < slot(&partial, "b"));
+    }
+
+    #[test]
+    fn external_links_lists_only_stubs() {
+        let outside = r#"["outside"]"#;
+        let data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b"), ("a", outside)]);
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        assert_eq!(layout.external_links, vec![outside]);
+        assert_eq!(layout.nodes[outside].kind.as_deref(), Some("link"));
+        assert_eq!(layout.nodes[outside].rank, None);
+        assert_eq!(layout.nodes["b"].kind.as_deref(), Some("asset"));
+        assert_eq!(layout.edges.len(), 2);
+    }
+
+    #[test]
+    fn kind_tells_sources_from_assets() {
+        let mut data = graph_data(
+            &[("source", "g"), ("observed", "g"), ("a", "g")],
+            &[("source", "a"), ("observed", "a")],
+        );
+        data.nodes.get_mut("source").unwrap().definition.isSource = true;
+        let observed = &mut data.nodes.get_mut("observed").unwrap().definition;
+        observed.isSource = true;
+        observed.isObservable = true;
+
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        let kind = |id: &str| layout.nodes[id].kind.as_deref();
+        assert_eq!(kind("source"), Some("source"));
+        assert_eq!(kind("observed"), Some("observable_source"));
+        assert_eq!(kind("a"), Some("asset"));
+        let json = serde_json::to_value(&layout).unwrap();
+        assert_eq!(json["nodes"]["source"]["kind"], "source");
+    }
+
+    #[test]
+    fn from_reader_streams_graph_data() {
+        let fixture: serde_json::Value =
+            serde_json::from_str(include_str!("../small_graph.json")).unwrap();
+        let bytes = serde_json::to_vec(&fixture["graphData"]).unwrap();
+
+        let data = GraphData::from_reader(std::io::Cursor::new(&bytes)).unwrap();
+        assert_eq!(data.nodes.len(), 10);
+        assert_eq!(data.downstream.len(), 2);
+
+        let mut trailing = bytes.clone();
+        trailing.extend_from_slice(b" {}");
+        assert!(GraphData::from_reader(std::io::Cursor::new(trailing)).is_err());
+        assert!(GraphData::from_reader(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn arrow_gap_moves_horizontal_edge_ends() {
+        let data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b")]);
+        let end = |arrow_gap: f32| {
+            let opts = LayoutAssetGraphOptions::builder()
+                .horizontal(true)
+                .arrow_gap(arrow_gap)
+                .build();
+            let layout = layout_asset_graph(&data, &opts).unwrap();
+            (layout.edges[0].to.clone(), layout.nodes["b"].bounds.x)
+        };
+        let (flush, card) = end(0.0);
+        assert_eq!(flush.x, card);
+        let (gapped, _) = end(12.0);
+        assert_eq!(gapped.x, flush.x - 12.0);
+        assert_eq!(gapped.y, flush.y);
+    }
+
+    #[test]
+    fn card_heights_for_each_kind_of_asset() {
+        for (is_source, is_observable, height) in [
+            (false, false, 156.0),
+            (false, true, 156.0),
+            (true, false, 102.0),
+            (true, true, 160.0),
+        ] {
+            let def = AssetNode {
+                isSource: is_source,
+                isObservable: is_observable,
+                ..Default::default()
+            };
+            let bounds = get_asset_node_dimensions(&def);
+            assert_eq!(
+                (bounds.width, bounds.height),
+                (265.0, height),
+                "isSource: {}, isObservable: {}",
+                is_source,
+                is_observable
+            );
         }
-    } else {
-        IBounds {
-            x: 0.0,
-            y: 0.0,
-            width: 106.0,
-            height: 90.0,
+    }
+
+    #[test]
+    fn header_bounds_run_along_the_top_of_the_group() {
+        let data = graph_data(&[("a", "g"), ("b", "h")], &[("a", "b")]);
+        let headers = |opts: LayoutAssetGraphOptions| {
+            let layout = layout_asset_graph(&data, &opts).unwrap();
+            layout
+                .groups
+                .into_values()
+                .map(|group| (group.bounds, group.header_bounds))
+                .collect::<Vec<_>>()
+        };
+
+        for (bounds, header) in headers(LayoutAssetGraphOptions::default()) {
+            assert_eq!((header.x, header.y), (bounds.x, bounds.y));
+            assert_eq!((header.width, header.height), (bounds.width, 70.0));
+        }
+        for (bounds, header) in headers(LayoutAssetGraphOptions::builder().horizontal(true).build())
+        {
+            assert_eq!((header.x, header.y), (bounds.x, bounds.y));
+            assert_eq!((header.width, header.height), (70.0, bounds.height));
+        }
+        let flat = LayoutAssetGraphOptions::builder()
+            .group_padding(IPoint { x: 0.0, y: 0.0 })
+            .build();
+        for (_, header) in headers(flat) {
+            assert_eq!(header.height, 0.0);
         }
     }
-}
 
-pub fn pad_bounds(a: &IBounds, padding: &IPoint) -> IBounds {
-    IBounds {
-        x: a.x - padding.x,
-        y: a.y - padding.y,
-        width: a.width + padding.x * 2.0,
-        height: a.height + padding.y * 2.0,
+    #[test]
+    fn size_matches_the_scanned_extent_plus_margin() {
+        let args: LayoutAssetGraphArgs =
+            serde_json::from_str(include_str!("../small_graph.json")).unwrap();
+        let layout = layout_asset_graph(&args.graphData, &args.opts).unwrap();
+        let boxes = layout
+            .nodes
+            .values()
+            .map(|node| &node.bounds)
+            .chain(layout.groups.values().map(|group| &group.bounds));
+        let (right, bottom) = boxes.fold((0.0f32, 0.0f32), |(right, bottom), b| {
+            (right.max(b.x + b.width), bottom.max(b.y + b.height))
+        });
+
+        let margin = args.opts.margin as f32;
+        let (width, height) = (layout.width as f32, layout.height as f32);
+        assert!(width >= right + margin - 1.0 && width <= right + margin + 1.0);
+        assert!(height >= bottom + margin - 1.0 && height <= bottom + margin + 1.0);
     }
-}
 
-pub fn extend_bounds(a: &IBounds, b: &IBounds) -> IBounds {
-    let xmin = a.x.min(b.x);
-    let ymin = a.y.min(b.y);
-    let xmax = (a.x + a.width).max(b.x + b.width);
-    let ymax = (a.y + a.height).max(b.y + b.height);
-    IBounds {
-        x: xmin,
-        y: ymin,
-        width: xmax - xmin,
-        height: ymax - ymin,
+    #[test]
+    fn partition_dimensions_grow_the_card() {
+        let height = |dimensions: Option<usize>| {
+            get_asset_node_dimensions(&AssetNode {
+                isPartitioned: true,
+                partitionDimensions: dimensions,
+                ..Default::default()
+            })
+            .height
+        };
+        assert_eq!(height(Some(3)) - height(Some(1)), 40.0);
+        assert_eq!(height(None), height(Some(2)));
+        let unpartitioned = get_asset_node_dimensions(&AssetNode::default()).height;
+        assert_eq!(height(Some(1)), unpartitioned + 20.0);
     }
-}
 
-pub const ASSET_NODE_NAME_MAX_LENGTH: usize = 28;
+    #[test]
+    fn ordered_node_ids_follow_columns_in_horizontal_layouts() {
+        let link = r#"["elsewhere"]"#;
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g"), ("d", "g")],
+            &[("a", "b"), ("a", "c"), ("b", "d"), ("d", link)],
+        );
+        let opts = LayoutAssetGraphOptions::builder().horizontal(true).build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+        let ids = &layout.ordered_node_ids;
 
-pub fn get_asset_node_dimensions(def: &AssetNode) -> IBounds {
-    let width: f32 = 265.0;
+        let mut expected: Vec<&String> = layout.nodes.keys().collect();
+        expected.sort_by(|x, y| {
+            let (x, y) = (&layout.nodes[*x].bounds, &layout.nodes[*y].bounds);
+            x.x.total_cmp(&y.x).then(x.y.total_cmp(&y.y))
+        });
+        assert_eq!(ids.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(ids.last().unwrap(), link);
+    }
 
-    if def.isSource && !def.isObservable {
-        IBounds {
-            x: 0.0,
-            y: 0.0,
-            width,
-            height: 102.0,
+    #[test]
+    fn edge_crossings_counts_a_bipartite_cross() {
+        let nodes = [("a", "g"), ("b", "g"), ("c", "g"), ("d", "g")];
+        let opts = LayoutAssetGraphOptions::builder()
+            .count_edge_crossings(true)
+            .build();
+        let crossings = |edges: &[(&str, &str)]| {
+            let data = graph_data(&nodes, edges);
+            let layout = layout_asset_graph(&data, &opts).unwrap();
+            layout.edge_crossings.unwrap()
+        };
+        // Every top node feeds every bottom one, so two of the edges must
+        // cross; edges sharing an end don't count.
+        assert_eq!(
+            crossings(&[("a", "c"), ("a", "d"), ("b", "c"), ("b", "d")]),
+            1
+        );
+        assert_eq!(crossings(&[("a", "c"), ("b", "d")]), 0);
+        assert_eq!(crossings(&[("a", "c"), ("a", "d"), ("b", "d")]), 0);
+
+        let data = graph_data(&nodes, &[("a", "c"), ("b", "d")]);
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        assert_eq!(layout.edge_crossings, None);
+    }
+
+    #[test]
+    fn group_order_places_groups_in_sequence() {
+        let data = graph_data(
+            &[("a", "g1"), ("b", "g1"), ("c", "g2"), ("d", "g3")],
+            &[("a", "b")],
+        );
+        let group_id = |name: &str| format!("group____loc__repo__{}", name);
+        let left_to_right = |order: &[&str]| {
+            let opts = LayoutAssetGraphOptions::builder()
+                .group_order(order.iter().map(|name| group_id(name)).collect())
+                .build();
+            let layout = layout_asset_graph(&data, &opts).unwrap();
+            // g1 runs down two ranks and may reach under its neighbours, so
+            // compare centres rather than left edges.
+            let center = |name: &str| {
+                let bounds = &layout.groups[&group_id(name)].bounds;
+                bounds.x + bounds.width / 2.0
+            };
+            let mut names = vec!["g1", "g2", "g3"];
+            names.sort_by(|x, y| center(x).total_cmp(&center(y)));
+            names
+        };
+        assert_eq!(left_to_right(&["g3", "g1", "g2"]), vec!["g3", "g1", "g2"]);
+        assert_eq!(left_to_right(&["g2", "g3", "g1"]), vec!["g2", "g3", "g1"]);
+        // Unlisted groups come after the listed ones, by id.
+        assert_eq!(left_to_right(&["g3"]), vec!["g3", "g1", "g2"]);
+    }
+
+    #[test]
+    fn validate_group_containment_flags_an_escaping_node() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "h")],
+            &[("a", "b"), ("b", "c")],
+        );
+        let mut layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        assert!(validate_group_containment(&layout).is_empty());
+
+        let group = &layout.groups["group____loc__repo__g"].bounds;
+        let past_the_right_edge = group.x + group.width;
+        layout.nodes.get_mut("b").unwrap().bounds.x = past_the_right_edge;
+        assert_eq!(validate_group_containment(&layout), vec!["b"]);
+    }
+
+    #[test]
+    fn cycles_lay_out_under_either_acyclicer() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g"), ("d", "g")],
+            &[("a", "b"), ("b", "c"), ("c", "a"), ("c", "d")],
+        );
+        for acyclicer in [Acyclicer::Greedy, Acyclicer::Dfs] {
+            let opts = LayoutAssetGraphOptions::builder()
+                .acyclicer(acyclicer)
+                .build();
+            let layout = layout_asset_graph(&data, &opts).unwrap();
+            let ends: Vec<(&str, &str)> = layout
+                .edges
+                .iter()
+                .map(|e| (e.fromId.as_str(), e.toId.as_str()))
+                .collect();
+            assert_eq!(
+                ends,
+                vec![("a", "b"), ("b", "c"), ("c", "a"), ("c", "d")],
+                "{:?}",
+                acyclicer
+            );
+            // Breaking a three-node cycle takes one reversal.
+            assert_eq!(layout.edges.iter().filter(|e| e.reversed).count(), 1);
         }
-    } else {
-        let mut height: f32 = 100.0; // top tags area + name + description
+    }
 
-        if def.isSource {
-            height += 30.0; // last observed
-        } else {
-            height += 26.0; // status row
-            if def.isPartitioned {
-                height += 40.0;
-            }
+    #[test]
+    fn to_react_flow_flattens_nodes_and_edges() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g")],
+            &[("a", "b"), ("a", "c")],
+        );
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        let flow = layout.to_react_flow();
+        assert_eq!(flow.nodes.len(), layout.nodes.len());
+        assert_eq!(flow.edges.len(), layout.edges.len());
+
+        let node = flow.nodes.iter().find(|node| node.id == "b").unwrap();
+        let bounds = &layout.nodes["b"].bounds;
+        assert_eq!((node.position.x, node.position.y), (bounds.x, bounds.y));
+        assert_eq!((node.width, node.height), (bounds.width, bounds.height));
+        assert_eq!(flow.edges[0].id, "a->b");
+        assert_eq!(
+            (flow.edges[0].source.as_str(), flow.edges[0].target.as_str()),
+            ("a", "b")
+        );
+    }
+
+    #[test]
+    fn link_height_sizes_stubs_both_ways() {
+        let link = r#"["elsewhere"]"#;
+        let data = graph_data(&[("a", "g")], &[("a", link)]);
+        let stub_height = |builder: OptionsBuilder| {
+            layout_asset_graph(&data, &builder.build()).unwrap().nodes[link]
+                .bounds
+                .height
+        };
+        let builder = LayoutAssetGraphOptions::builder;
+        assert_eq!(stub_height(builder()), 90.0);
+        assert_eq!(stub_height(builder().link_height(40.0)), 40.0);
+        assert_eq!(
+            stub_height(builder().horizontal(true).link_height(40.0)),
+            40.0
+        );
+    }
+
+    #[test]
+    fn header_padding_goes_on_the_header_side() {
+        let members = IBounds {
+            x: 100.0,
+            y: 100.0,
+            width: 200.0,
+            height: 50.0,
+        };
+        let padding = IPoint { x: 10.0, y: 70.0 };
+        // (left, top, right, bottom) gaps around the members.
+        let gaps = |rankdir| {
+            let b = pad_group_bounds(&members, &padding, rankdir);
+            (
+                members.x - b.x,
+                members.y - b.y,
+                b.x + b.width - (members.x + members.width),
+                b.y + b.height - (members.y + members.height),
+            )
+        };
+        assert_eq!(gaps(RankDir::TB), (10.0, 70.0, 10.0, 70.0));
+        assert_eq!(gaps(RankDir::LR), (70.0, 10.0, 10.0, 10.0));
+
+        // The same goes for laid out groups.
+        let data = graph_data(&[("a", "g"), ("b", "h")], &[("a", "b")]);
+        let opts = LayoutAssetGraphOptions::builder().horizontal(true).build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+        let (a, group) = (
+            &layout.nodes["a"].bounds,
+            &layout.groups[layout.nodes["a"].group.as_ref().unwrap()].bounds,
+        );
+        assert!((a.x - group.x - opts.group_padding.y).abs() < 0.01);
+        assert!((a.y - group.y - opts.group_padding.x).abs() < 0.01);
+    }
+
+    #[test]
+    fn max_nodes_rejects_larger_graphs_up_front() {
+        let ids: Vec<String> = (0..10).map(|i| format!("n{}", i)).collect();
+        let nodes: Vec<(&str, &str)> = ids.iter().map(|id| (id.as_str(), "g")).collect();
+        let edges: Vec<(&str, &str)> = ids
+            .windows(2)
+            .map(|pair| (pair[0].as_str(), pair[1].as_str()))
+            .collect();
+        let data = graph_data(&nodes, &edges);
+
+        let limited = |limit| {
+            let opts = LayoutAssetGraphOptions::builder().max_nodes(limit).build();
+            layout_asset_graph(&data, &opts).err()
+        };
+        assert_eq!(
+            limited(5),
+            Some(LayoutError::TooLarge {
+                count: 10,
+                limit: 5
+            })
+        );
+        assert_eq!(limited(10), None);
+    }
+
+    #[test]
+    fn one_edge_of_a_two_cycle_is_reversed() {
+        let data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b"), ("b", "a")]);
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        assert_eq!(layout.edges.len(), 2);
+        let reversed: Vec<&AssetLayoutEdge> = layout.edges.iter().filter(|e| e.reversed).collect();
+        assert_eq!(reversed.len(), 1);
+
+        // It still runs from its source, which is the lower card.
+        let edge = reversed[0];
+        let (from, to) = (&layout.nodes[&edge.fromId], &layout.nodes[&edge.toId]);
+        assert!(from.bounds.y > to.bounds.y);
+        assert!(edge.from.y > edge.to.y);
+    }
+
+    #[test]
+    fn edge_insets_shift_the_endpoints() {
+        let data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b")]);
+        let ends = |builder: OptionsBuilder| {
+            let layout = layout_asset_graph(&data, &builder.build()).unwrap();
+            let edge = &layout.edges[0];
+            (edge.from.clone(), edge.to.clone())
+        };
+        let builder = LayoutAssetGraphOptions::builder;
+
+        let (from, to) = ends(builder());
+        let (inset_from, inset_to) = ends(builder().edge_insets(EdgeInsets {
+            top: 5.0,
+            bottom: 10.0,
+            left: 40.0,
+            right: 0.0,
+        }));
+        assert_eq!(inset_from.x - from.x, 40.0 - 24.0);
+        assert_eq!(inset_from.y - from.y, 30.0 - 10.0);
+        assert_eq!(inset_to.x - to.x, 40.0 - 24.0);
+        assert_eq!(inset_to.y - to.y, 5.0 - 20.0);
+
+        let (from, to) = ends(builder().horizontal(true));
+        let (inset_from, inset_to) = ends(builder().horizontal(true).edge_insets(EdgeInsets {
+            top: 0.0,
+            bottom: 0.0,
+            left: 12.0,
+            right: 8.0,
+        }));
+        assert_eq!(inset_from.x - from.x, -8.0);
+        assert_eq!(inset_to.x - to.x, 12.0);
+        assert_eq!((inset_from.y, inset_to.y), (from.y, to.y));
+    }
+
+    #[test]
+    fn hide_sources_leaves_out_unobserved_sources() {
+        let mut data = graph_data(
+            &[("source", "g"), ("observed", "g"), ("a", "g")],
+            &[("source", "a"), ("observed", "a")],
+        );
+        data.nodes.get_mut("source").unwrap().definition.isSource = true;
+        let observed = &mut data.nodes.get_mut("observed").unwrap().definition;
+        observed.isSource = true;
+        observed.isObservable = true;
+
+        let opts = LayoutAssetGraphOptions::builder()
+            .hide_sources(true)
+            .build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+        let mut ids: Vec<&String> = layout.nodes.keys().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "observed"]);
+        assert_eq!(layout.edges.len(), 1);
+        assert_eq!(layout.edges[0].fromId, "observed");
+        assert!(layout.external_links.is_empty());
+
+        let shown = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        assert!(shown.nodes.contains_key("source"));
+    }
+
+    #[test]
+    fn anchors_sit_at_the_middle_of_each_side() {
+        let data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b")]);
+        let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        for node in layout.nodes.values() {
+            let (b, anchors) = (&node.bounds, &node.anchors);
+            let point = |p: &IPoint| (p.x, p.y);
+            assert_eq!(point(&anchors.top), (b.x + b.width / 2.0, b.y));
+            assert_eq!(
+                point(&anchors.bottom),
+                (b.x + b.width / 2.0, b.y + b.height)
+            );
+            assert_eq!(point(&anchors.left), (b.x, b.y + b.height / 2.0));
+            assert_eq!(point(&anchors.right), (b.x + b.width, b.y + b.height / 2.0));
         }
+    }
 
-        height += 30.0; // tags beneath
+    #[test]
+    fn meta_comes_back_on_the_layout() {
+        let data = graph_data(&[("a", "g")], &[]);
+        let opts = LayoutAssetGraphOptions::builder()
+            .meta("view:lineage".to_string())
+            .build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+        assert_eq!(layout.meta.as_deref(), Some("view:lineage"));
+        let plain = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        assert_eq!(plain.meta, None);
+    }
 
-        IBounds {
-            x: 0.0,
-            y: 0.0,
-            width,
-            height,
+    #[test]
+    fn edges_come_out_sorted_by_endpoints() {
+        // graph_data builds fresh HashMaps, so each copy iterates the
+        // downstream map in its own order.
+        let nodes = [("a", "g"), ("b", "g"), ("c", "g"), ("d", "g"), ("e", "g")];
+        let edges = [("d", "e"), ("a", "c"), ("b", "c"), ("a", "b"), ("c", "e")];
+        let ends = || {
+            let data = graph_data(&nodes, &edges);
+            let layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+            layout
+                .edges
+                .into_iter()
+                .map(|e| (e.fromId, e.toId))
+                .collect::<Vec<_>>()
+        };
+        let first = ends();
+        let mut sorted = first.clone();
+        sorted.sort();
+        assert_eq!(first, sorted);
+        for _ in 0..5 {
+            assert_eq!(ends(), first);
         }
     }
+
+    #[test]
+    fn center_edges_join_the_node_centres() {
+        let data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b")]);
+        let opts = LayoutAssetGraphOptions::builder()
+            .edge_style(EdgeEndpointStyle::Center)
+            .build();
+        let layout = layout_asset_graph(&data, &opts).unwrap();
+        let centre = |id: &str| {
+            let b = &layout.nodes[id].bounds;
+            (b.x + b.width / 2.0, b.y + b.height / 2.0)
+        };
+        let edge = &layout.edges[0];
+        assert_eq!((edge.from.x, edge.from.y), centre("a"));
+        assert_eq!((edge.to.x, edge.to.y), centre("b"));
+    }
+
+    #[test]
+    fn fit_to_scales_into_the_box() {
+        let data = graph_data(&[("a", "g"), ("b", "g")], &[("a", "b")]);
+        let mut layout = layout_asset_graph(&data, &LayoutAssetGraphOptions::default()).unwrap();
+        layout.width = 1000;
+        layout.height = 500;
+        let before = layout.nodes["b"].bounds.clone();
+        let before_from = (layout.edges[0].from.x, layout.edges[0].from.y);
+
+        assert_eq!(layout.fit_to(100.0, 100.0), 0.1);
+        assert_eq!((layout.width, layout.height), (100, 50));
+        let after = &layout.nodes["b"].bounds;
+        let near = |a: f32, b: f32| (a - b).abs() < 1e-3;
+        assert!(near(after.x, before.x * 0.1) && near(after.y, before.y * 0.1));
+        assert!(near(after.width, before.width * 0.1));
+        assert!(near(after.height, before.height * 0.1));
+        let from = &layout.edges[0].from;
+        assert!(near(from.x, before_from.0 * 0.1) && near(from.y, before_from.1 * 0.1));
+    }
+
+    #[test]
+    fn align_picks_which_child_a_branch_lines_up_with() {
+        let data = graph_data(
+            &[("a", "g"), ("b", "g"), ("c", "g")],
+            &[("a", "b"), ("a", "c")],
+        );
+        let place = |align: Align| {
+            let opts = LayoutAssetGraphOptions::builder().align(align).build();
+            let layout = layout_asset_graph(&data, &opts).unwrap();
+            let x = |id: &str| {
+                let b = &layout.nodes[id].bounds;
+                b.x + b.width / 2.0
+            };
+            let (left, right) = (x("b").min(x("c")), x("b").max(x("c")));
+            (x("a"), left, right)
+        };
+        let (ul, left, _) = place(Align::UL);
+        assert_eq!(ul, left);
+        let (ur, _, right) = place(Align::UR);
+        assert_eq!(ur, right);
+    }
 }